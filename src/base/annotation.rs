@@ -0,0 +1,305 @@
+use std::fmt;
+use std::time::Duration;
+
+/**
+ * The colors of square highlights and arrows.
+ */
+#[derive(Debug,PartialEq,Clone,Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AnnotationColor {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+}
+
+impl AnnotationColor {
+
+    fn from_char(c: char) -> Option<AnnotationColor> {
+        match c {
+            'R' => Some(AnnotationColor::Red),
+            'G' => Some(AnnotationColor::Green),
+            'Y' => Some(AnnotationColor::Yellow),
+            'B' => Some(AnnotationColor::Blue),
+            _ => None
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            AnnotationColor::Red => 'R',
+            AnnotationColor::Green => 'G',
+            AnnotationColor::Yellow => 'Y',
+            AnnotationColor::Blue => 'B',
+        }
+    }
+
+}
+
+/**
+ * A highlighted square, from `[%csl Ra1,Gb2]`.
+ */
+#[derive(Debug,PartialEq,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SquareHighlight {
+    pub color: AnnotationColor,
+    pub square: String,
+}
+
+/**
+ * An arrow between two squares, from `[%cal Ge2e4]`.
+ */
+#[derive(Debug,PartialEq,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Arrow {
+    pub color: AnnotationColor,
+    pub from: String,
+    pub to: String,
+}
+
+/**
+ * An engine evaluation, from the point of view of white.
+ */
+#[derive(Debug,PartialEq,Clone,Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Eval {
+    Centipawns(i32),
+    /**
+     * Mate in the given number of moves, negative if black mates.
+     */
+    Mate(i32),
+}
+
+/**
+ * The commands embedded in the comment of a move, like `[%clk 1:05:00] [%eval +0.35]`.
+ */
+#[derive(Debug,PartialEq,Clone,Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MoveCommands {
+    /**
+     * The clock time remaining after the move (`%clk`).
+     */
+    pub clock: Option<Duration>,
+    /**
+     * The engine evaluation after the move (`%eval`).
+     */
+    pub eval: Option<Eval>,
+    pub eval_depth: Option<u32>,
+    /**
+     * The time spent on the move (`%emt`).
+     */
+    pub elapsed: Option<Duration>,
+    pub highlights: Vec<SquareHighlight>,
+    pub arrows: Vec<Arrow>,
+}
+
+impl MoveCommands {
+
+    pub fn is_empty(&self) -> bool {
+        *self == MoveCommands::default()
+    }
+
+    /**
+     * Parses a command, returns false if it's unknown or its value is not valid.
+     */
+    fn parse_command(&mut self, name: &str, value: &str) -> bool {
+        match name {
+            "clk" => parse_duration(value).map(|d| self.clock = Some(d)).is_some(),
+            "emt" => parse_duration(value).map(|d| self.elapsed = Some(d)).is_some(),
+            "eval" => {
+                let mut parts = value.splitn(2, ',');
+                let eval = parts.next().and_then(parse_eval);
+                let depth = parts.next().map(|it| it.trim().parse::<u32>());
+
+                match (eval, depth) {
+                    (Some(eval), None) => self.eval = Some(eval),
+                    (Some(eval), Some(Ok(depth))) => {
+                        self.eval = Some(eval);
+                        self.eval_depth = Some(depth);
+                    },
+                    _ => return false
+                }
+                true
+            },
+            "csl" => {
+                let highlights: Option<Vec<SquareHighlight>> = value.split(',').map(|it| {
+                    let (color, squares) = parse_colored_squares(it.trim(), 1)?;
+                    Some(SquareHighlight{color, square: squares[0].to_string()})
+                }).collect();
+
+                highlights.map(|hs| self.highlights.extend(hs)).is_some()
+            },
+            "cal" => {
+                let arrows: Option<Vec<Arrow>> = value.split(',').map(|it| {
+                    let (color, squares) = parse_colored_squares(it.trim(), 2)?;
+                    Some(Arrow{color, from: squares[0].to_string(), to: squares[1].to_string()})
+                }).collect();
+
+                arrows.map(|arrows| self.arrows.extend(arrows)).is_some()
+            },
+            _ => false
+        }
+    }
+
+}
+
+impl fmt::Display for MoveCommands {
+
+    /**
+     * Writes the commands in PGN format, separated by spaces.
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut commands = Vec::new();
+
+        if let Some(clock) = self.clock {
+            commands.push(format!("[%clk {}]", format_duration(clock)));
+        }
+        if let Some(elapsed) = self.elapsed {
+            commands.push(format!("[%emt {}]", format_duration(elapsed)));
+        }
+        if let Some(eval) = self.eval {
+            let value = match eval {
+                Eval::Centipawns(cp) => format!("{:.2}", cp as f64 / 100.0),
+                Eval::Mate(moves) => format!("#{}", moves)
+            };
+            match self.eval_depth {
+                Some(depth) => commands.push(format!("[%eval {},{}]", value, depth)),
+                None => commands.push(format!("[%eval {}]", value))
+            }
+        }
+        if !self.highlights.is_empty() {
+            let highlights: Vec<String> = self.highlights.iter()
+                .map(|it| format!("{}{}", it.color.to_char(), it.square)).collect();
+            commands.push(format!("[%csl {}]", highlights.join(",")));
+        }
+        if !self.arrows.is_empty() {
+            let arrows: Vec<String> = self.arrows.iter()
+                .map(|it| format!("{}{}{}", it.color.to_char(), it.from, it.to)).collect();
+            commands.push(format!("[%cal {}]", arrows.join(",")));
+        }
+
+        write!(f, "{}", commands.join(" "))
+    }
+
+}
+
+/**
+ * Extracts the known commands from a comment.
+ * # Returns
+ * the commands, if any, and the remaining text of the comment. Unknown or invalid commands are left in the text.
+ */
+pub fn extract_commands(comment: &str) -> (Option<MoveCommands>, String) {
+    if !comment.contains("[%") {
+        return (None, comment.to_string());
+    }
+
+    let mut commands = MoveCommands::default();
+    let mut parts: Vec<&str> = Vec::new();
+    let mut rest = comment;
+
+    while let Some(start) = rest.find("[%") {
+        let end = match rest[start..].find(']') {
+            Some(end) => start + end,
+            None => break
+        };
+
+        let command = rest[start + 2..end].trim();
+        let (name, value) = match command.find(char::is_whitespace) {
+            Some(i) => (&command[..i], command[i..].trim()),
+            None => (command, "")
+        };
+
+        if commands.parse_command(name, value) {
+            parts.push(&rest[..start]);
+        } else {
+            parts.push(&rest[..end + 1]);
+        }
+        rest = &rest[end + 1..];
+    }
+    parts.push(rest);
+
+    let text: Vec<&str> = parts.iter().map(|it| it.trim()).filter(|it| !it.is_empty()).collect();
+
+    if commands.is_empty() {
+        (None, comment.to_string())
+    } else {
+        (Some(commands), text.join(" "))
+    }
+}
+
+/**
+ * Parses a time like `1:05:00`, `5:00` or `0:00:07.3`.
+ */
+fn parse_duration(value: &str) -> Option<Duration> {
+    let parts: Vec<&str> = value.split(':').collect();
+
+    if parts.len() > 3 {
+        return None;
+    }
+
+    let mut seconds: u64 = 0;
+    for part in &parts[..parts.len() - 1] {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+
+    let last = parts[parts.len() - 1];
+    let (secs, millis) = match last.find('.') {
+        Some(i) => {
+            let fraction = &last[i + 1..];
+            if fraction.is_empty() || fraction.len() > 3 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            (&last[..i], format!("{:0<3}", fraction).parse::<u32>().ok()?)
+        },
+        None => (last, 0)
+    };
+
+    seconds = seconds * 60 + secs.parse::<u64>().ok()?;
+
+    Some(Duration::new(seconds, millis * 1_000_000))
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let time = format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+
+    match duration.subsec_millis() {
+        0 => time,
+        millis => format!("{}.{}", time, format!("{:03}", millis).trim_end_matches('0'))
+    }
+}
+
+fn parse_eval(value: &str) -> Option<Eval> {
+    let value = value.trim();
+
+    if let Some(moves) = value.strip_prefix('#') {
+        return moves.parse::<i32>().ok().map(Eval::Mate);
+    }
+
+    value.parse::<f64>().ok().filter(|it| it.is_finite()).map(|pawns| Eval::Centipawns((pawns * 100.0).round() as i32))
+}
+
+fn is_square(square: &str) -> bool {
+    let bytes = square.as_bytes();
+    bytes.len() == 2 && (b'a'..=b'h').contains(&bytes[0]) && (b'1'..=b'8').contains(&bytes[1])
+}
+
+/**
+ * Parses a color followed by the given number of squares, like `Ge2e4`.
+ */
+fn parse_colored_squares(value: &str, count: usize) -> Option<(AnnotationColor, Vec<&str>)> {
+    let color = AnnotationColor::from_char(value.chars().next()?)?;
+    let squares_text = &value[1..];
+
+    if !squares_text.is_ascii() || squares_text.len() != count * 2 {
+        return None;
+    }
+
+    let squares: Vec<&str> = (0..count).map(|i| &squares_text[i * 2..i * 2 + 2]).collect();
+
+    if squares.iter().all(|it| is_square(it)) {
+        Some((color, squares))
+    } else {
+        None
+    }
+}
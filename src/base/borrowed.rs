@@ -0,0 +1,506 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::str;
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+use memmap2::Mmap;
+
+use base::annotation::extract_commands;
+use base::fen::*;
+use base::nag::split_suffix;
+use base::notation::parse_iccf_move;
+use base::game::*;
+use base::parser::{ChessParserConfig, normalize_variation};
+use base::position::*;
+use base::tag::*;
+
+type Int = i16;
+
+/**
+ * The codes of the NAGs of the suffix annotations, that are from 1 to 6, as static strings.
+ */
+const SUFFIX_NAG_CODES: [&str; 7] = ["0", "1", "2", "3", "4", "5", "6"];
+
+/**
+ * Maps a file in memory, so it can be parsed with `ChessParser::parse_bytes`.
+ * Compressed files cannot be mapped, since they must be decompressed.
+ *
+ * The file must not be modified while it's mapped.
+ */
+pub fn map_file(file: &File) -> io::Result<Mmap> {
+    unsafe { Mmap::map(file) }
+}
+
+/**
+ * A game whose strings are borrowed from the parsed input, except for tag values with escaped chars.
+ * Comments and variations are the raw text in the input, so they can contain new lines.
+ */
+pub struct BorrowedGame<'b> {
+    tags: Vec<(&'b str, Cow<'b, str>)>,
+    moves: Vec<&'b str>,
+    comments: Vec<(Int, &'b str)>,
+    variations: Vec<(Int, Vec<&'b str>)>,
+    after_variations_comments: Vec<(Int, Int, &'b str)>,
+    game_result: &'b str,
+    nags: Vec<(Int, Vec<&'b str>)>,
+}
+
+impl <'b> BorrowedGame<'b> {
+
+    fn new() -> BorrowedGame<'b> {
+        BorrowedGame{tags: Vec::new(), moves: Vec::new(), comments: Vec::new(), variations: Vec::new(),
+            after_variations_comments: Vec::new(), game_result: "", nags: Vec::new()}
+    }
+
+    pub fn get_tags(&self) -> &Vec<(&'b str, Cow<'b, str>)> {
+        &self.tags
+    }
+
+    pub fn get_tag(&self, key: &str) -> Option<&str> {
+        self.tags.iter().find(|it| it.0 == key).map(|it| it.1.as_ref())
+    }
+
+    pub fn get_moves(&self) -> &Vec<&'b str> {
+        &self.moves
+    }
+
+    pub fn get_before_moves_comment(&self) -> Option<&'b str> {
+        self.get_comment(-1)
+    }
+
+    /**
+     * # Arguments
+     * * `after_move` is zero based.
+     */
+    pub fn get_comment(&self, after_move: Int) -> Option<&'b str> {
+        self.comments.iter().find(|it| it.0 == after_move).map(|it| it.1)
+    }
+
+    pub fn get_game_result(&self) -> &'b str {
+        self.game_result
+    }
+
+    pub fn initial_position(&self) -> Result<ChessPosition,String> {
+        match self.get_tag(&Tag::FEN.to_string()) {
+            Some(fen) => FEN_PARSER.parse(fen),
+            _ => Result::Ok(ChessPosition::initial_position())
+        }
+    }
+
+    /**
+     * # Arguments
+     * * `after_move` is zero based.
+     */
+    pub fn get_nags(&self, after_move: Int) -> Option<&Vec<&'b str>> {
+        self.nags.iter().find(|it| it.0 == after_move).map(|it| &it.1)
+    }
+
+    /**
+     * # Arguments
+     * * `after_move` is zero based.
+     */
+    pub fn get_variations(&self, after_move: Int) -> Option<&Vec<&'b str>> {
+        self.variations.iter().find(|it| it.0 == after_move).map(|it| &it.1)
+    }
+
+    /**
+     * # Arguments
+     * * `after_move` is zero based.
+     * * `after_variation_move` is zero based
+     */
+    pub fn get_after_variation_comment(&self, after_move: Int, after_variation_move: Int) -> Option<&'b str> {
+        self.after_variations_comments.iter()
+            .find(|it| it.0 == after_move && it.1 == after_variation_move)
+            .map(|it| it.2)
+    }
+
+    /**
+     * Converts to an owned game, new lines in comments and variations are replaced by spaces
+     * variations are normalized and commands are extracted from comments, like `ChessParserIterator` does.
+     */
+    pub fn to_game(&self) -> ChessGameImpl {
+        let mut comments = HashMap::new();
+        let mut commands = HashMap::new();
+        for &(m, c) in &self.comments {
+            match extract_commands(&one_line(c)) {
+                (Some(move_commands), text) => {
+                    commands.insert(m, move_commands);
+                    if !text.is_empty() {
+                        comments.insert(m, text);
+                    }
+                },
+                (None, text) => {
+                    comments.insert(m, text);
+                }
+            }
+        }
+
+        let mut variations = HashMap::new();
+        for (m, vs) in &self.variations {
+            variations.insert(*m, vs.iter().map(|v| normalize_variation(v)).collect());
+        }
+
+        let mut after_variations_comments: HashMap<Int,HashMap<Int,String>> = HashMap::new();
+        for &(m, v, c) in &self.after_variations_comments {
+            after_variations_comments.entry(m).or_default().insert(v, one_line(c));
+        }
+
+        let mut nags = HashMap::new();
+        for (m, ns) in &self.nags {
+            nags.insert(*m, ns.iter().map(|n| n.to_string()).collect());
+        }
+
+        ChessGameImpl::new(self.tags_map(), self.moves.iter().map(|m| m.to_string()).collect(), comments,
+            variations, after_variations_comments, self.game_result.to_string(), nags).with_commands(commands)
+    }
+
+    fn tags_map(&self) -> IndexMap<String,String> {
+        self.tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn last_move_index(&self) -> Int {
+        self.moves.len() as Int - 1
+    }
+
+    fn add_comment(&mut self, comment: &'b str) {
+        let last_move_index = self.last_move_index();
+
+        let variations_count = self.get_variations(last_move_index).map_or(0, |vs| vs.len());
+
+        if variations_count > 0 {
+            let last_variation_index = variations_count as Int - 1;
+            self.after_variations_comments.retain(|it| it.0 != last_move_index || it.1 != last_variation_index);
+            self.after_variations_comments.push((last_move_index, last_variation_index, comment));
+        } else {
+            self.comments.retain(|it| it.0 != last_move_index);
+            self.comments.push((last_move_index, comment));
+        }
+    }
+
+    fn add_variation(&mut self, variation: &'b str) {
+        let last_move_index = self.last_move_index();
+        push_to(&mut self.variations, last_move_index, variation);
+    }
+
+    fn add_nag(&mut self, nag: &'b str) {
+        let last_move_index = self.last_move_index();
+        push_to(&mut self.nags, last_move_index, nag);
+    }
+
+}
+
+fn push_to<'b>(values: &mut Vec<(Int, Vec<&'b str>)>, index: Int, value: &'b str) {
+    match values.iter_mut().find(|it| it.0 == index) {
+        Some(it) => it.1.push(value),
+        None => values.push((index, vec![value]))
+    }
+}
+
+fn one_line(s: &str) -> String {
+    s.replace('\r', "").replace('\n', " ")
+}
+
+/**
+ * Returns the position of the first occurrence of c starting from pos, or the end of the bytes.
+ */
+fn find_from(bytes: &[u8], pos: usize, c: u8) -> usize {
+    bytes[pos..].iter().position(|&it| it == c).map_or(bytes.len(), |it| pos + it)
+}
+
+fn is_whitespace(c: u8) -> bool {
+    c == b' ' || c == b'\t' || c == b'\n' || c == b'\r'
+}
+
+fn is_token_end(c: u8) -> bool {
+    is_whitespace(c) || c == b'{' || c == b'(' || c == b')' || c == b'$' || c == b'[' || c == b';' || c == b'<'
+}
+
+/**
+ * Removes the backslashes escaping quotes and backslashes in a tag value.
+ */
+fn unescape_tag_value(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c)
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+fn is_result(token: &str) -> bool {
+    token == "1-0" || token == "0-1" || token == "1/2-1/2" || token == "*"
+}
+
+/**
+ * A parser over an in memory input (for example a memory mapped file), the games borrow their strings from the input,
+ * so no allocation is done for tags, moves, comments and variations.
+ */
+pub struct BorrowedParserIterator<'b> {
+    config: Arc<ChessParserConfig>,
+    input: &'b str,
+    pos: usize,
+}
+
+impl <'b> BorrowedParserIterator<'b> {
+
+    pub fn new(config: Arc<ChessParserConfig>, input: &'b str) -> Self {
+        BorrowedParserIterator{config, input, pos: 0}
+    }
+
+    /**
+     * The input must be UTF-8.
+     */
+    pub fn from_bytes(config: Arc<ChessParserConfig>, input: &'b [u8]) -> Result<Self, String> {
+        let input = input.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(input);
+
+        match str::from_utf8(input) {
+            Ok(s) => Ok(BorrowedParserIterator::new(config, s)),
+            Err(e) => Err(format!("Invalid UTF-8 input: {}, other encodings are supported only by `parse`.", e))
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).cloned()
+    }
+
+    fn is_line_start(&self, pos: usize) -> bool {
+        pos == 0 || self.input.as_bytes()[pos - 1] == b'\n'
+    }
+
+    /**
+     * Skips white spaces and escape lines, and returns the number of new lines found, escape lines are not counted.
+     */
+    fn skip_whitespace(&mut self) -> usize {
+        let bytes = self.input.as_bytes();
+        let mut new_lines = 0;
+        while self.pos < bytes.len() {
+            if bytes[self.pos] == b'%' && self.is_line_start(self.pos) {
+                self.pos = (self.find_byte(b'\n') + 1).min(bytes.len());
+                continue;
+            }
+            if !is_whitespace(bytes[self.pos]) {
+                break;
+            }
+            if bytes[self.pos] == b'\n' {
+                new_lines += 1;
+            }
+            self.pos += 1;
+        }
+        new_lines
+    }
+
+    /**
+     * Returns the position of the first occurrence of c starting from the current position, or the end of the input.
+     */
+    fn find_byte(&self, c: u8) -> usize {
+        find_from(self.input.as_bytes(), self.pos, c)
+    }
+
+    fn parse_tag(&mut self, game: &mut BorrowedGame<'b>) {
+        let bytes = self.input.as_bytes();
+        // skips the [
+        self.pos += 1;
+        self.skip_whitespace();
+
+        let key_start = self.pos;
+        while self.pos < bytes.len() && !is_whitespace(bytes[self.pos]) && bytes[self.pos] != b'"' && bytes[self.pos] != b']' {
+            self.pos += 1;
+        }
+        let key = &self.input[key_start..self.pos];
+
+        self.skip_whitespace();
+
+        let mut value = "";
+        if self.peek() == Some(b'"') {
+            self.pos += 1;
+            let start = self.pos;
+            while self.pos < bytes.len() && bytes[self.pos] != b'"' {
+                self.pos += if bytes[self.pos] == b'\\' { 2 } else { 1 };
+            }
+            self.pos = self.pos.min(bytes.len());
+            value = &self.input[start..self.pos];
+        }
+
+        self.pos = (self.find_byte(b']') + 1).min(bytes.len());
+
+        if !key.is_empty() && !value.is_empty() {
+            game.tags.push((key, unescape_tag_value(value)));
+        }
+    }
+
+    /**
+     * Returns the position of the parenthesis closing the variation starting at the current position.
+     */
+    fn find_variation_end(&self) -> usize {
+        let bytes = self.input.as_bytes();
+        let mut depth = 0;
+        let mut pos = self.pos;
+        while pos < bytes.len() {
+            match bytes[pos] {
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return pos;
+                    }
+                },
+                b'{' => pos = find_from(bytes, pos, b'}'),
+                b';' => pos = find_from(bytes, pos, b'\n'),
+                b'<' => pos = find_from(bytes, pos, b'>'),
+                b'%' if self.is_line_start(pos) => pos = find_from(bytes, pos, b'\n'),
+                _ => ()
+            }
+            pos += 1;
+        }
+        bytes.len()
+    }
+
+    fn parse_movetext(&mut self, game: &mut BorrowedGame<'b>, skip: bool) {
+        let bytes = self.input.as_bytes();
+
+        loop {
+            let new_lines = self.skip_whitespace();
+
+            if self.pos >= bytes.len() {
+                break;
+            }
+
+            // an empty line after moves ends the game, like in ChessParserIterator
+            if new_lines >= 2 && !game.moves.is_empty() {
+                break;
+            }
+
+            match bytes[self.pos] {
+                // the tags of the next game
+                b'[' => break,
+                b'{' => {
+                    self.pos += 1;
+                    let end = self.find_byte(b'}');
+                    if !skip && !self.config.ignore_comments {
+                        game.add_comment(self.input[self.pos..end].trim_end());
+                    }
+                    self.pos = (end + 1).min(bytes.len());
+                },
+                b'(' => {
+                    let end = self.find_variation_end();
+                    if !skip && !self.config.ignore_variations {
+                        game.add_variation(self.input[self.pos + 1..end].trim_end());
+                    }
+                    self.pos = (end + 1).min(bytes.len());
+                },
+                b';' => {
+                    self.pos += 1;
+                    let end = self.find_byte(b'\n');
+                    if !skip && !self.config.ignore_comments {
+                        game.add_comment(self.input[self.pos..end].trim());
+                    }
+                    self.pos = end;
+                },
+                b'<' => self.pos = (self.find_byte(b'>') + 1).min(bytes.len()),
+                b')' => self.pos += 1,
+                b'$' => {
+                    self.pos += 1;
+                    let start = self.pos;
+                    while self.pos < bytes.len() && bytes[self.pos].is_ascii_digit() {
+                        self.pos += 1;
+                    }
+                    if !skip && self.pos > start {
+                        game.add_nag(&self.input[start..self.pos]);
+                    }
+                },
+                _ => {
+                    let start = self.pos;
+                    while self.pos < bytes.len() && !is_token_end(bytes[self.pos]) {
+                        self.pos += 1;
+                    }
+                    let token = &self.input[start..self.pos];
+
+                    if is_result(token) {
+                        game.game_result = token;
+                        break;
+                    }
+
+                    let (mv, nag) = split_suffix(strip_move_number(token, self.config.numeric_moves()));
+
+                    if !skip && !mv.is_empty() {
+                        game.moves.push(mv);
+                        if let Some(nag) = nag {
+                            game.add_nag(SUFFIX_NAG_CODES[nag.code() as usize]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /**
+     * Returns the next game and true if it must be skipped, or None if the input is ended.
+     */
+    fn parse_game(&mut self) -> Option<(BorrowedGame<'b>, bool)> {
+        self.skip_whitespace();
+
+        if self.pos >= self.input.len() {
+            return None;
+        }
+
+        let mut game = BorrowedGame::new();
+
+        while self.peek() == Some(b'[') {
+            self.parse_tag(&mut game);
+            self.skip_whitespace();
+        }
+
+        // the tags and the game are copied only for the filters that need them
+        let skip = self.config.has_filters() && !self.config.accept_tags(&game.tags_map());
+
+        self.parse_movetext(&mut game, skip);
+
+        // errors of the game filter reject the game, since there's no error list
+        let skip = skip || (self.config.needs_game() &&
+            !self.config.game_filter.as_ref().unwrap().accept_game(&game.to_game()).unwrap_or(false));
+
+        Some((game, skip))
+    }
+
+}
+
+/**
+ * Strips the move number (for example "12." or "12...") from a token. A token of digits is a move only if `numeric`
+ * moves are read, otherwise it's dropped.
+ */
+fn strip_move_number(token: &str, numeric: bool) -> &str {
+    let without_digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+
+    if without_digits.len() < token.len() && without_digits.starts_with('.') {
+        without_digits.trim_start_matches('.')
+    } else if without_digits.is_empty() && !(numeric && parse_iccf_move(token).is_some()) {
+        // only a move number without dots, not a move in ICCF numeric notation
+        without_digits
+    } else {
+        token
+    }
+}
+
+impl <'b> Iterator for BorrowedParserIterator<'b> {
+    type Item = BorrowedGame<'b>;
+
+    fn next(&mut self) -> Option<BorrowedGame<'b>> {
+        loop {
+            match self.parse_game() {
+                Some((_, true)) => continue,
+                Some((game, false)) => return Some(game),
+                None => return None
+            }
+        }
+    }
+}
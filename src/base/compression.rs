@@ -0,0 +1,185 @@
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Error;
+use std::io::Read;
+use std::io::Result;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "bzip2")]
+use bzip2::bufread::MultiBzDecoder;
+#[cfg(feature = "gzip")]
+use flate2::bufread::MultiGzDecoder;
+#[cfg(feature = "xz")]
+use xz2::bufread::XzDecoder;
+#[cfg(feature = "zstd")]
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+#[derive(Display,Debug,PartialEq,Clone,Copy)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/**
+ * Detects the compression from the magic bytes at the start of a stream.
+ */
+pub fn detect_compression(header: &[u8]) -> Compression {
+    if header.starts_with(GZIP_MAGIC) {
+        Compression::Gzip
+    } else if header.starts_with(BZIP2_MAGIC) {
+        Compression::Bzip2
+    } else if header.starts_with(XZ_MAGIC) {
+        Compression::Xz
+    } else if header.starts_with(ZSTD_MAGIC) {
+        Compression::Zstd
+    } else {
+        Compression::None
+    }
+}
+
+fn feature_name(compression: Compression) -> &'static str {
+    match compression {
+        Compression::None => "",
+        Compression::Gzip => "gzip",
+        Compression::Bzip2 => "bzip2",
+        Compression::Xz => "xz",
+        Compression::Zstd => "zstd",
+    }
+}
+
+/**
+ * A reader that counts the bytes read from the underlying reader.
+ */
+pub struct CountingReader<R: Read> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl <R: Read> Read for CountingReader<R> {
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.count.fetch_add(count as u64, Ordering::Relaxed);
+        Ok(count)
+    }
+
+}
+
+type Source<R> = BufReader<CountingReader<R>>;
+
+enum Decoder<R: Read> {
+    Plain(Source<R>),
+    #[cfg(feature = "gzip")]
+    Gzip(MultiGzDecoder<Source<R>>),
+    #[cfg(feature = "bzip2")]
+    Bzip2(MultiBzDecoder<Source<R>>),
+    #[cfg(feature = "xz")]
+    Xz(XzDecoder<Source<R>>),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdDecoder<'static, Source<R>>),
+}
+
+/**
+ * A reader that transparently decompresses gzip, bzip2, xz and zstd streams.
+ * The compression is detected from the magic bytes on the first read, plain streams are passed through.
+ * Every format needs its cargo feature (`gzip`, `bzip2`, `xz`, `zstd`), otherwise reading fails.
+ * Concatenated streams (for example files created with `cat a.pgn.gz b.pgn.gz`) are fully read.
+ */
+pub struct CompressedReader<R: Read> {
+    source: Option<R>,
+    decoder: Option<Decoder<R>>,
+    read_bytes: Arc<AtomicU64>,
+}
+
+impl <R: Read> CompressedReader<R> {
+
+    pub fn new(reader: R) -> CompressedReader<R> {
+        CompressedReader{source: Some(reader), decoder: None, read_bytes: Arc::new(AtomicU64::new(0))}
+    }
+
+    /**
+     * The number of bytes read from the underlying (compressed) reader.
+     */
+    pub fn read_bytes(&self) -> Arc<AtomicU64> {
+        self.read_bytes.clone()
+    }
+
+    /**
+     * The detected compression, it's None until the first read.
+     */
+    pub fn compression(&self) -> Option<Compression> {
+        self.decoder.as_ref().map(|decoder| match decoder {
+            Decoder::Plain(_) => Compression::None,
+            #[cfg(feature = "gzip")]
+            Decoder::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "bzip2")]
+            Decoder::Bzip2(_) => Compression::Bzip2,
+            #[cfg(feature = "xz")]
+            Decoder::Xz(_) => Compression::Xz,
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(_) => Compression::Zstd,
+        })
+    }
+
+    fn init(&mut self) -> Result<Option<Decoder<R>>> {
+        let reader = match self.source.take() {
+            Some(r) => r,
+            None => return Ok(None)
+        };
+
+        let mut source = BufReader::new(CountingReader{inner: reader, count: self.read_bytes.clone()});
+
+        let compression = detect_compression(source.fill_buf()?);
+
+        let decoder = match compression {
+            Compression::None => Decoder::Plain(source),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Decoder::Gzip(MultiGzDecoder::new(source)),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Decoder::Bzip2(MultiBzDecoder::new(source)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Decoder::Xz(XzDecoder::new_multi_decoder(source)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Decoder::Zstd(ZstdDecoder::with_buffer(source)?),
+            #[allow(unreachable_patterns)]
+            other => return Err(Error::other(format!("{} compressed input is not supported, the {} feature is not enabled.",
+                other, feature_name(other))))
+        };
+
+        Ok(Some(decoder))
+    }
+
+}
+
+impl <R: Read> Read for CompressedReader<R> {
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.decoder.is_none() {
+            self.decoder = self.init()?;
+        }
+
+        match self.decoder {
+            Some(Decoder::Plain(ref mut r)) => r.read(buf),
+            #[cfg(feature = "gzip")]
+            Some(Decoder::Gzip(ref mut r)) => r.read(buf),
+            #[cfg(feature = "bzip2")]
+            Some(Decoder::Bzip2(ref mut r)) => r.read(buf),
+            #[cfg(feature = "xz")]
+            Some(Decoder::Xz(ref mut r)) => r.read(buf),
+            #[cfg(feature = "zstd")]
+            Some(Decoder::Zstd(ref mut r)) => r.read(buf),
+            // the source has been consumed by a failed initialization
+            None => Ok(0)
+        }
+    }
+
+}
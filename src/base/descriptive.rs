@@ -0,0 +1,461 @@
+use base::notation::{ChessMove, Castling};
+use base::position::{ChessPosition, ChessColor, Piece, PieceType, Square, char_to_piece_type, other_color,
+    piece_color, piece_to_char, piece_type_to_char};
+
+/**
+ * The languages of the descriptive notation.
+ */
+#[derive(Debug,PartialEq,Eq,Clone,Copy)]
+pub enum DescriptiveLanguage {
+    /**
+     * Like `P-K4`, `N-KB3` or `QxP`, `Kt` is read as a knight.
+     */
+    English,
+    /**
+     * Like `P4R`, `C3AR` or `DxP`.
+     */
+    Spanish,
+}
+
+/**
+ * The pieces that start on each file, they name the files.
+ */
+const FILE_PIECES: [char; 8] = ['R', 'N', 'B', 'Q', 'K', 'B', 'N', 'R'];
+
+/**
+ * The words that can follow a move, like in `QxP ch`, they are kept with the move when the input is read.
+ */
+const SUFFIXES: [&str; 7] = ["ch", "dis", "dbl", "mate", "e.p.", "ep", "jaque"];
+
+/**
+ * Whether a word is the check, mate or en passant word written after a descriptive move, like `ch` in `QxP ch`.
+ */
+pub(crate) fn is_descriptive_suffix(word: &str) -> bool {
+    SUFFIXES.contains(&word)
+}
+
+/**
+ * A piece as named by the descriptive notation, like `P`, `KBP`, `QR` or `N/Q2`.
+ */
+struct Designator {
+    piece: PieceType,
+    /**
+     * The files of a pawn.
+     */
+    files: Option<Vec<u8>>,
+    /**
+     * The side of the board of a piece, true for the king side. The side of a bishop is given by the color of its
+     * squares.
+     */
+    king_side: Option<bool>,
+    squares: Option<Vec<Square>>,
+}
+
+impl Designator {
+
+    fn matches(&self, piece: Piece, square: &Square) -> bool {
+        let color = match piece_color(&piece) {
+            Some(color) => color,
+            None => return false
+        };
+
+        piece_type(piece) == self.piece &&
+            self.files.as_ref().is_none_or(|it| it.contains(&square.file())) &&
+            self.king_side.is_none_or(|it| it == king_side(self.piece, color, square)) &&
+            self.squares.as_ref().is_none_or(|it| it.contains(square))
+    }
+
+}
+
+/**
+ * Finds the legal move written in descriptive notation, with its squares and its promotion, to apply it with
+ * `ChessPosition::apply_coordinate_move`.
+ */
+pub fn parse_descriptive(position: &ChessPosition, descriptive: &str, language: DescriptiveLanguage)
+        -> Result<(Square, Square, Option<PieceType>), String> {
+    let color = position.active_color;
+    let text = strip_suffixes(descriptive.split_whitespace().next().unwrap_or(""));
+    let rank = if color == ChessColor::White { 1 } else { 8 };
+
+    match text {
+        "O-O" | "0-0" => return Ok((square(5, rank), square(7, rank), None)),
+        "O-O-O" | "0-0-0" => return Ok((square(5, rank), square(3, rank), None)),
+        _ => ()
+    }
+
+    let invalid = || format!("invalid descriptive move {}", descriptive);
+    let mut text = normalize(text, language).ok_or_else(invalid)?;
+
+    let promotion = split_promotion(&mut text, language);
+
+    let (from_text, to_text, capture) = if let Some(i) = text.find(['x', ':']) {
+        (&text[..i], &text[i + 1..], true)
+    } else if let Some(i) = text.find('-') {
+        (&text[..i], &text[i + 1..], false)
+    } else if language == DescriptiveLanguage::Spanish {
+        let i = text.find(|c: char| c.is_ascii_digit()).ok_or_else(invalid)?;
+        (&text[..i], &text[i..], false)
+    } else {
+        return Err(invalid());
+    };
+
+    let from = parse_designator(from_text, color, language).ok_or_else(invalid)?;
+    let captured = if capture { Some(parse_designator(to_text, color, language).ok_or_else(invalid)?) } else { None };
+    let squares = if capture { vec![] } else { parse_square(to_text, color, language).ok_or_else(invalid)? };
+
+    let last_rank = if color == ChessColor::White { 8 } else { 1 };
+
+    let found = position.legal_moves_where(|from_square, to| {
+        let piece = position.board.get_piece(from_square.file(), from_square.rank());
+        let piece_type = piece_type(piece);
+
+        // castling is written only as O-O or O-O-O
+        if !from.matches(piece, from_square) ||
+                piece_type == PieceType::King && (from_square.file() as i8 - to.file() as i8).abs() == 2 {
+            return false;
+        }
+
+        let matches = match captured {
+            Some(ref captured) => {
+                let en_passant = piece_type == PieceType::Pawn && position.en_passant_target_square == Some(*to);
+                let captured_square = if en_passant { square(to.file(), from_square.rank()) } else { *to };
+                captured.matches(position.board.get_piece(captured_square.file(), captured_square.rank()),
+                    &captured_square)
+            },
+            None => squares.contains(to) && position.board.get_piece(to.file(), to.rank()) == Piece::None
+        };
+
+        let promoted = piece_type == PieceType::Pawn && to.rank() == last_rank;
+        matches && (promotion.is_none() || promoted)
+    });
+
+    match found.len() {
+        1 => {
+            let (from_square, to) = found[0];
+            let piece = position.board.get_piece(from_square.file(), from_square.rank());
+            let promoted = piece_type(piece) == PieceType::Pawn && to.rank() == last_rank;
+            Ok((from_square, to, if promoted { Some(promotion.unwrap_or(PieceType::Queen)) } else { None }))
+        },
+        0 => Err(format!("no legal move matches {}", descriptive)),
+        _ => Err(format!("ambiguous move {}", descriptive))
+    }
+}
+
+/**
+ * Writes a move in descriptive notation, as short as it can be without being ambiguous.
+ *
+ * # Arguments
+ * * `position` is the position before the move.
+ */
+pub fn descriptive_move(position: &ChessPosition, chess_move: &ChessMove, language: DescriptiveLanguage) -> String {
+    let color = position.active_color;
+
+    let mut result = match chess_move.castling {
+        Some(Castling::KingSide) => "O-O".to_string(),
+        Some(Castling::QueenSide) => "O-O-O".to_string(),
+        None => {
+            let piece = position.board.get_piece(chess_move.from.file(), chess_move.from.rank());
+            let from_names = designators(piece_type(piece), &chess_move.from, color, color, language);
+
+            let to_names = if chess_move.capture {
+                let target = position.board.get_piece(chess_move.to.file(), chess_move.to.rank());
+                // en passant
+                let (captured, square) = if target == Piece::None {
+                    (PieceType::Pawn, square(chess_move.to.file(), chess_move.from.rank()))
+                } else {
+                    (piece_type(target), chess_move.to)
+                };
+                designators(captured, &square, other_color(color), color, language)
+            } else {
+                vec![square_name(&chess_move.to, color, false, language),
+                    square_name(&chess_move.to, color, true, language)]
+            };
+
+            let promotion = chess_move.promotion.map(|it| format!("={}", letter(it, language))).unwrap_or_default();
+
+            let mut candidates: Vec<String> = Vec::new();
+            for from in &from_names {
+                for to in &to_names {
+                    let separator = if chess_move.capture {
+                        "x"
+                    } else if language == DescriptiveLanguage::Spanish && !from.contains('/') {
+                        ""
+                    } else {
+                        "-"
+                    };
+                    candidates.push(format!("{}{}{}{}", from, separator, to, promotion));
+                }
+            }
+            candidates.sort_by_key(|it| it.chars().count());
+
+            let expected = (chess_move.from, chess_move.to, chess_move.promotion);
+            candidates.iter()
+                .find(|it| parse_descriptive(position, it, language).ok() == Some(expected))
+                .or_else(|| candidates.last())
+                .cloned()
+                .unwrap_or_default()
+        }
+    };
+
+    if chess_move.checkmate {
+        result.push('#');
+    } else if chess_move.check {
+        result.push('+');
+    }
+
+    result
+}
+
+/**
+ * The names of a piece, from the shortest to the one with its square.
+ *
+ * # Arguments
+ * * `color` is the color of the piece.
+ * * `mover` is the color of the player moving, the squares are seen from its side.
+ */
+fn designators(piece: PieceType, square: &Square, color: ChessColor, mover: ChessColor,
+        language: DescriptiveLanguage) -> Vec<String> {
+    let letter = letter(piece, language);
+
+    let mut names = vec![letter.to_string()];
+
+    if piece == PieceType::Pawn {
+        for full in &[false, true] {
+            let file = file_name(square.file(), *full, language);
+            names.push(match language {
+                DescriptiveLanguage::English => format!("{}{}", file, letter),
+                DescriptiveLanguage::Spanish => format!("{}{}", letter, file),
+            });
+        }
+    } else if piece == PieceType::Knight || piece == PieceType::Bishop || piece == PieceType::Rook {
+        let side = side_letter(king_side(piece, color, square), language);
+        names.push(match language {
+            DescriptiveLanguage::English => format!("{}{}", side, letter),
+            DescriptiveLanguage::Spanish => format!("{}{}", letter, side),
+        });
+    }
+
+    names.push(format!("{}/{}", letter, square_name(square, mover, true, language)));
+    names.dedup();
+    names
+}
+
+/**
+ * The name of a square seen from the side of a player, like `KB3` or `3AR`. Without `full` the side of the board
+ * is not written, like `B3`.
+ */
+fn square_name(square: &Square, color: ChessColor, full: bool, language: DescriptiveLanguage) -> String {
+    let rank = if color == ChessColor::White { square.rank() } else { 9 - square.rank() };
+    let file = file_name(square.file(), full, language);
+
+    match language {
+        DescriptiveLanguage::English => format!("{}{}", file, rank),
+        DescriptiveLanguage::Spanish => format!("{}{}", rank, file),
+    }
+}
+
+/**
+ * The name of a file, by the piece starting on it, like `KB` or `AR`. The queen and king files have no side.
+ */
+fn file_name(file: u8, full: bool, language: DescriptiveLanguage) -> String {
+    let piece = letter(char_to_piece_type(FILE_PIECES[file as usize - 1]), language);
+
+    if !full || file == 4 || file == 5 {
+        return piece.to_string();
+    }
+
+    let side = side_letter(file >= 5, language);
+    match language {
+        DescriptiveLanguage::English => format!("{}{}", side, piece),
+        DescriptiveLanguage::Spanish => format!("{}{}", piece, side),
+    }
+}
+
+fn side_letter(king_side: bool, language: DescriptiveLanguage) -> char {
+    letter(if king_side { PieceType::King } else { PieceType::Queen }, language)
+}
+
+fn letter(piece: PieceType, language: DescriptiveLanguage) -> char {
+    match (language, piece) {
+        (DescriptiveLanguage::Spanish, PieceType::Knight) => 'C',
+        (DescriptiveLanguage::Spanish, PieceType::Bishop) => 'A',
+        (DescriptiveLanguage::Spanish, PieceType::Rook) => 'T',
+        (DescriptiveLanguage::Spanish, PieceType::Queen) => 'D',
+        (DescriptiveLanguage::Spanish, PieceType::King) => 'R',
+        (_, piece) => piece_type_to_char(piece)
+    }
+}
+
+/**
+ * Replaces the letters of the pieces with the English ones, `Kt` with `N`. `None` if there's an unknown letter.
+ */
+fn normalize(text: &str, language: DescriptiveLanguage) -> Option<String> {
+    let text = text.replace("Kt", "N");
+
+    text.chars().map(|c| match (language, c) {
+        (DescriptiveLanguage::Spanish, 'C') => Some('N'),
+        (DescriptiveLanguage::Spanish, 'A') => Some('B'),
+        (DescriptiveLanguage::Spanish, 'T') => Some('R'),
+        (DescriptiveLanguage::Spanish, 'D') => Some('Q'),
+        (DescriptiveLanguage::Spanish, 'R') => Some('K'),
+        (DescriptiveLanguage::Spanish, 'P') => Some('P'),
+        (DescriptiveLanguage::Spanish, _) if c.is_ascii_uppercase() => None,
+        (DescriptiveLanguage::English, 'P') | (DescriptiveLanguage::English, 'N') | (DescriptiveLanguage::English, 'B') |
+            (DescriptiveLanguage::English, 'R') | (DescriptiveLanguage::English, 'Q') |
+            (DescriptiveLanguage::English, 'K') => Some(c),
+        (DescriptiveLanguage::English, _) if c.is_ascii_uppercase() => None,
+        _ if c.is_ascii_digit() || c == '-' || c == 'x' || c == ':' || c == '/' || c == '=' => Some(c),
+        _ => None
+    }).collect()
+}
+
+/**
+ * Removes the check, mate and en passant marks and the suffix annotations at the end of a move.
+ */
+fn strip_suffixes(text: &str) -> &str {
+    let mut text = text.trim_end_matches(['!', '?']);
+
+    loop {
+        let stripped = SUFFIXES.iter().chain(["+", "#"].iter())
+            .filter_map(|it| text.strip_suffix(*it))
+            .next();
+
+        match stripped {
+            Some(stripped) if !stripped.is_empty() => text = stripped,
+            _ => return text
+        }
+    }
+}
+
+/**
+ * Removes the promotion from a normalized move, written as `=Q`, `/Q` or, in English, `Q` after the rank.
+ */
+fn split_promotion(text: &mut String, language: DescriptiveLanguage) -> Option<PieceType> {
+    let last = text.chars().last()?;
+    let before = text.chars().rev().nth(1)?;
+
+    let promotion = match char_to_piece_type(last) {
+        PieceType::Knight | PieceType::Bishop | PieceType::Rook | PieceType::Queen => char_to_piece_type(last),
+        _ => return None
+    };
+
+    if before == '=' || before == '/' {
+        text.truncate(text.len() - 2);
+    } else if language == DescriptiveLanguage::English && before.is_ascii_digit() && text.contains('-') {
+        text.truncate(text.len() - 1);
+    } else {
+        return None;
+    }
+
+    Some(promotion)
+}
+
+/**
+ * Parses a normalized piece name, like `P`, `KBP` (`PAR` in Spanish), `QR` (`TD`) or `N/Q2`.
+ */
+fn parse_designator(text: &str, color: ChessColor, language: DescriptiveLanguage) -> Option<Designator> {
+    let (name, squares) = match text.find('/') {
+        Some(i) => (&text[..i], Some(parse_square(&text[i + 1..], color, language)?)),
+        None => (text, None)
+    };
+
+    let chars: Vec<char> = name.chars().collect();
+
+    let (piece, qualifier) = match language {
+        DescriptiveLanguage::English => {
+            let (&last, rest) = chars.split_last()?;
+            (last, rest)
+        },
+        DescriptiveLanguage::Spanish => {
+            let (&first, rest) = chars.split_first()?;
+            (first, rest)
+        }
+    };
+
+    let piece = char_to_piece_type(piece);
+    let mut designator = Designator{piece, files: None, king_side: None, squares};
+
+    match (piece, qualifier) {
+        (PieceType::None, _) => return None,
+        (_, []) => (),
+        (PieceType::Pawn, file) => designator.files = Some(parse_file(file, language)?),
+        (PieceType::Knight, [side]) | (PieceType::Bishop, [side]) | (PieceType::Rook, [side]) =>
+            designator.king_side = Some(parse_side(*side)?),
+        _ => return None
+    }
+
+    Some(designator)
+}
+
+/**
+ * Parses a normalized square name, like `KB3` (`3AR` in Spanish). A square without the side, like `B3`, can be one
+ * of two squares.
+ */
+fn parse_square(text: &str, color: ChessColor, language: DescriptiveLanguage) -> Option<Vec<Square>> {
+    let chars: Vec<char> = text.chars().collect();
+
+    let (rank, file) = match language {
+        DescriptiveLanguage::English => {
+            let (&last, rest) = chars.split_last()?;
+            (last, rest)
+        },
+        DescriptiveLanguage::Spanish => {
+            let (&first, rest) = chars.split_first()?;
+            (first, rest)
+        }
+    };
+
+    let rank = rank.to_digit(10).filter(|it| *it >= 1 && *it <= 8)? as u8;
+    let rank = if color == ChessColor::White { rank } else { 9 - rank };
+
+    Some(parse_file(file, language)?.into_iter().map(|file| square(file, rank)).collect())
+}
+
+fn parse_file(chars: &[char], language: DescriptiveLanguage) -> Option<Vec<u8>> {
+    let (piece, side) = match (language, chars) {
+        (_, [piece]) => (*piece, None),
+        (DescriptiveLanguage::English, [side, piece]) | (DescriptiveLanguage::Spanish, [piece, side]) =>
+            (*piece, Some(parse_side(*side)?)),
+        _ => return None
+    };
+
+    if side.is_some() && (piece == 'Q' || piece == 'K') {
+        return None;
+    }
+
+    let files: Vec<u8> = (1..9)
+        .filter(|&file| FILE_PIECES[file as usize - 1] == piece && side.is_none_or(|it| it == (file >= 5)))
+        .collect();
+
+    if files.is_empty() { None } else { Some(files) }
+}
+
+fn parse_side(c: char) -> Option<bool> {
+    match c {
+        'K' => Some(true),
+        'Q' => Some(false),
+        _ => None
+    }
+}
+
+/**
+ * Whether a piece is on the king side of the board. A bishop is on the side where it started, given by the color of
+ * its squares.
+ */
+fn king_side(piece: PieceType, color: ChessColor, square: &Square) -> bool {
+    if piece == PieceType::Bishop {
+        // the king bishops start on f1 and f8
+        let parity = if color == ChessColor::White { 1 } else { 0 };
+        (square.file() + square.rank()) % 2 == parity
+    } else {
+        square.file() >= 5
+    }
+}
+
+fn piece_type(piece: Piece) -> PieceType {
+    char_to_piece_type(piece_to_char(piece).to_ascii_uppercase())
+}
+
+fn square(file: u8, rank: u8) -> Square {
+    Square::new(file, rank).unwrap()
+}
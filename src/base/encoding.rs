@@ -0,0 +1,172 @@
+use std::io;
+use std::io::Write;
+use std::str;
+
+#[derive(Display,Debug,PartialEq,Clone,Copy)]
+pub enum Encoding {
+    #[strum(serialize="UTF-8")]
+    Utf8,
+    #[strum(serialize="ISO-8859-1")]
+    Latin1,
+    #[strum(serialize="Windows-1252")]
+    Windows1252,
+}
+
+pub fn encoding_from_name(name: &str) -> Result<Encoding, String> {
+    match name.to_lowercase().as_ref() {
+        "utf-8" | "utf8" => Ok(Encoding::Utf8),
+        "latin1" | "latin-1" | "iso-8859-1" | "iso8859-1" => Ok(Encoding::Latin1),
+        "windows-1252" | "cp1252" => Ok(Encoding::Windows1252),
+        _ => Err(format!("Unknown encoding {}.", name))
+    }
+}
+
+/**
+ * The chars of the Windows-1252 bytes from 0x80 to 0x9F, the other bytes are the same of ISO-8859-1.
+ * Undefined bytes are mapped to the control char with the same code, like browsers do.
+ */
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+const UNMAPPABLE: u8 = b'?';
+
+/**
+ * Decodes the bytes appending them to `s`.
+ * # Returns
+ * false if the bytes are not valid in the encoding (only for UTF-8), in that case invalid sequences are
+ * replaced by U+FFFD.
+ */
+pub fn decode(bytes: &[u8], encoding: Encoding, s: &mut String) -> bool {
+    match encoding {
+        Encoding::Utf8 => match str::from_utf8(bytes) {
+            Ok(decoded) => {
+                s.push_str(decoded);
+                true
+            },
+            Err(_) => {
+                s.push_str(&String::from_utf8_lossy(bytes));
+                false
+            }
+        },
+        Encoding::Latin1 => {
+            s.extend(bytes.iter().map(|&b| b as char));
+            true
+        },
+        Encoding::Windows1252 => {
+            s.extend(bytes.iter().map(|&b| if (0x80..0xA0).contains(&b) { WINDOWS_1252[b as usize - 0x80] } else { b as char }));
+            true
+        }
+    }
+}
+
+/**
+ * Decodes the bytes as UTF-8 if they are valid, otherwise as Windows-1252, that is a superset of the printable
+ * chars of ISO-8859-1.
+ * # Returns
+ * the encoding used.
+ */
+pub fn decode_auto(bytes: &[u8], s: &mut String) -> Encoding {
+    match str::from_utf8(bytes) {
+        Ok(decoded) => {
+            s.push_str(decoded);
+            Encoding::Utf8
+        },
+        Err(_) => {
+            decode(bytes, Encoding::Windows1252, s);
+            Encoding::Windows1252
+        }
+    }
+}
+
+fn encode_char(c: char, encoding: Encoding) -> u8 {
+    let code = c as u32;
+    match encoding {
+        Encoding::Utf8 => panic!("UTF-8 chars are not single bytes."),
+        Encoding::Latin1 => if code < 0x100 { code as u8 } else { UNMAPPABLE },
+        Encoding::Windows1252 => if code < 0x80 || (0xA0..0x100).contains(&code) {
+            code as u8
+        } else {
+            WINDOWS_1252.iter().position(|&it| it == c).map_or(UNMAPPABLE, |i| 0x80 + i as u8)
+        }
+    }
+}
+
+/**
+ * Encodes a string, chars that cannot be represented in the encoding are replaced by '?'.
+ */
+pub fn encode(s: &str, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Utf8 => s.as_bytes().to_vec(),
+        _ => s.chars().map(|c| encode_char(c, encoding)).collect()
+    }
+}
+
+/**
+ * A writer that encodes the UTF-8 text written to it. An UTF-8 char split between two writes is handled.
+ */
+pub struct EncodingWriter<W: Write> {
+    w: W,
+    encoding: Encoding,
+    // the start of an UTF-8 char not completely written
+    pending: Vec<u8>,
+}
+
+impl <W: Write> EncodingWriter<W> {
+
+    pub fn new(w: W, encoding: Encoding) -> EncodingWriter<W> {
+        EncodingWriter{w, encoding, pending: Vec::new()}
+    }
+
+}
+
+impl <W: Write> Write for EncodingWriter<W> {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.encoding == Encoding::Utf8 {
+            return self.w.write(buf);
+        }
+
+        self.pending.extend_from_slice(buf);
+
+        let mut encoded = Vec::with_capacity(self.pending.len());
+        let mut start = 0;
+
+        loop {
+            match str::from_utf8(&self.pending[start..]) {
+                Ok(s) => {
+                    encoded.extend(encode(s, self.encoding));
+                    start = self.pending.len();
+                    break;
+                },
+                Err(e) => {
+                    let valid_end = start + e.valid_up_to();
+                    encoded.extend(encode(str::from_utf8(&self.pending[start..valid_end]).unwrap(), self.encoding));
+                    match e.error_len() {
+                        Some(len) => {
+                            encoded.push(UNMAPPABLE);
+                            start = valid_end + len;
+                        },
+                        // an incomplete char at the end
+                        None => {
+                            start = valid_end;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.pending.drain(..start);
+        self.w.write_all(&encoded)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+
+}
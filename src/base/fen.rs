@@ -167,4 +167,48 @@ impl FENParser {
             board: chessboard, en_passant_target_square: en_passant_target_square})
     }
 
-}
\ No newline at end of file
+}
+
+/**
+ * Writes a position in Forsyth-Edwards Notation.
+ */
+pub fn to_fen(position: &ChessPosition) -> String {
+    let mut fen = String::new();
+
+    for rank in (1..=8).rev() {
+        let mut empty = 0;
+        for file in 1..=8 {
+            match position.board.get_piece(file, rank) {
+                Piece::None => empty += 1,
+                piece => {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen.push(piece_to_char(piece));
+                }
+            }
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if rank > 1 {
+            fen.push('/');
+        }
+    }
+
+    fen.push_str(if position.active_color == ChessColor::White { " w " } else { " b " });
+
+    let castling: String = [(position.white_king_side_castling, 'K'), (position.white_queen_side_castling, 'Q'),
+            (position.black_king_side_castling, 'k'), (position.black_queen_side_castling, 'q')].iter()
+        .filter(|it| it.0).map(|it| it.1).collect();
+    fen.push_str(if castling.is_empty() { "-" } else { &castling });
+
+    match position.en_passant_target_square {
+        Some(square) => fen.push_str(&format!(" {}", square)),
+        None => fen.push_str(" -")
+    }
+
+    fen.push_str(&format!(" {} {}", position.half_move_clock, position.full_move_number));
+    fen
+}
@@ -0,0 +1,261 @@
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+
+use base::game::ChessGame;
+use base::position::ChessPosition;
+
+/**
+ * A filter of the parsed games.
+ *
+ * The tags are checked as soon as they are parsed, so games rejected by `accept_tags` are skipped without
+ * parsing their moves. `accept_game` is called only for the games whose tags are accepted, and only if
+ * `needs_game` is true.
+ */
+pub trait GameFilter: Send + Sync {
+
+    fn accept_tags(&self, _tags: &IndexMap<String,String>) -> bool {
+        true
+    }
+
+    /**
+     * # Returns
+     * an error if the game cannot be checked, for example when a move cannot be applied,
+     * the game is rejected and the error is recorded by the parser.
+     */
+    fn accept_game(&self, _game: &dyn ChessGame) -> Result<bool,String> {
+        Ok(true)
+    }
+
+    /**
+     * True if `accept_game` must be called, that is if the filter needs moves, comments or positions.
+     */
+    fn needs_game(&self) -> bool {
+        false
+    }
+
+}
+
+impl <F: GameFilter + ?Sized> GameFilter for Arc<F> {
+
+    fn accept_tags(&self, tags: &IndexMap<String,String>) -> bool {
+        (**self).accept_tags(tags)
+    }
+
+    fn accept_game(&self, game: &dyn ChessGame) -> Result<bool,String> {
+        (**self).accept_game(game)
+    }
+
+    fn needs_game(&self) -> bool {
+        (**self).needs_game()
+    }
+
+}
+
+/**
+ * A filter on the tags only.
+ */
+pub struct TagFilter<F: Fn(&IndexMap<String,String>) -> bool + Send + Sync> {
+    filter: F,
+}
+
+impl <F: Fn(&IndexMap<String,String>) -> bool + Send + Sync> TagFilter<F> {
+
+    pub fn new(filter: F) -> Self {
+        TagFilter{filter}
+    }
+
+}
+
+impl <F: Fn(&IndexMap<String,String>) -> bool + Send + Sync> GameFilter for TagFilter<F> {
+
+    fn accept_tags(&self, tags: &IndexMap<String,String>) -> bool {
+        (self.filter)(tags)
+    }
+
+}
+
+/**
+ * A filter on the whole game, for example on moves or comments.
+ */
+pub struct MovesFilter<F: Fn(&dyn ChessGame) -> bool + Send + Sync> {
+    filter: F,
+}
+
+impl <F: Fn(&dyn ChessGame) -> bool + Send + Sync> MovesFilter<F> {
+
+    pub fn new(filter: F) -> Self {
+        MovesFilter{filter}
+    }
+
+}
+
+impl <F: Fn(&dyn ChessGame) -> bool + Send + Sync> GameFilter for MovesFilter<F> {
+
+    fn accept_game(&self, game: &dyn ChessGame) -> Result<bool,String> {
+        Ok((self.filter)(game))
+    }
+
+    fn needs_game(&self) -> bool {
+        true
+    }
+
+}
+
+/**
+ * Accepts the games reaching a position, only the board is compared.
+ */
+pub struct PositionFilter {
+    position: ChessPosition,
+}
+
+impl PositionFilter {
+
+    pub fn new(position: ChessPosition) -> Self {
+        PositionFilter{position}
+    }
+
+}
+
+impl GameFilter for PositionFilter {
+
+    fn accept_game(&self, game: &dyn ChessGame) -> Result<bool,String> {
+        let mut p = game.initial_position()?;
+        for (count, mv) in game.get_moves().iter().enumerate() {
+            if let Some(e) = p.apply_move(mv) {
+                return Err(format!("move n. {}: {}", count + 1, e));
+            }
+            if p.board == self.position.board {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn needs_game(&self) -> bool {
+        true
+    }
+
+}
+
+/**
+ * Accepts the games accepted by all the filters.
+ */
+pub struct And {
+    filters: Vec<Box<dyn GameFilter>>,
+}
+
+impl And {
+
+    pub fn new(filters: Vec<Box<dyn GameFilter>>) -> Self {
+        And{filters}
+    }
+
+}
+
+impl GameFilter for And {
+
+    fn accept_tags(&self, tags: &IndexMap<String,String>) -> bool {
+        self.filters.iter().all(|f| f.accept_tags(tags))
+    }
+
+    fn accept_game(&self, game: &dyn ChessGame) -> Result<bool,String> {
+        for f in self.filters.iter().filter(|f| f.needs_game()) {
+            if !f.accept_game(game)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn needs_game(&self) -> bool {
+        self.filters.iter().any(|f| f.needs_game())
+    }
+
+}
+
+/**
+ * Accepts the games accepted by at least one of the filters.
+ */
+pub struct Or {
+    filters: Vec<Box<dyn GameFilter>>,
+}
+
+impl Or {
+
+    pub fn new(filters: Vec<Box<dyn GameFilter>>) -> Self {
+        Or{filters}
+    }
+
+}
+
+impl GameFilter for Or {
+
+    fn accept_tags(&self, tags: &IndexMap<String,String>) -> bool {
+        self.filters.iter().any(|f| f.accept_tags(tags))
+    }
+
+    fn accept_game(&self, game: &dyn ChessGame) -> Result<bool,String> {
+        let mut error = None;
+
+        for f in &self.filters {
+            // the tags must be checked again, since only one of the filters may have accepted them
+            if !f.accept_tags(game.get_tags()) {
+                continue;
+            }
+            if !f.needs_game() {
+                return Ok(true);
+            }
+            match f.accept_game(game) {
+                Ok(true) => return Ok(true),
+                Ok(false) => (),
+                Err(e) => error = error.or(Some(e))
+            }
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(false)
+        }
+    }
+
+    fn needs_game(&self) -> bool {
+        self.filters.iter().any(|f| f.needs_game())
+    }
+
+}
+
+/**
+ * Accepts the games rejected by the filter.
+ */
+pub struct Not {
+    filter: Box<dyn GameFilter>,
+}
+
+impl Not {
+
+    pub fn new(filter: Box<dyn GameFilter>) -> Self {
+        Not{filter}
+    }
+
+}
+
+impl GameFilter for Not {
+
+    fn accept_tags(&self, tags: &IndexMap<String,String>) -> bool {
+        // when the game is needed, the tags alone are not enough to reject it
+        self.filter.needs_game() || !self.filter.accept_tags(tags)
+    }
+
+    fn accept_game(&self, game: &dyn ChessGame) -> Result<bool,String> {
+        if !self.filter.accept_tags(game.get_tags()) {
+            return Ok(true);
+        }
+        Ok(!self.filter.accept_game(game)?)
+    }
+
+    fn needs_game(&self) -> bool {
+        self.filter.needs_game()
+    }
+
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
+use base::annotation::MoveCommands;
 use base::fen::*;
+use base::nag::Nag;
 use base::position::*;
 use base::tag::*;
 
@@ -32,6 +34,15 @@ pub trait ChessGame {
      */
     fn get_nags(&self, after_move: Int) -> Option<&Vec<String>>;
 
+    /**
+     * The NAGs with a standard code, see `get_nags` for all of them.
+     * # Arguments
+     * * `after_move` is zero based.
+     */
+    fn get_typed_nags(&self, after_move: Int) -> Vec<Nag> {
+        self.get_nags(after_move).map_or_else(Vec::new, |nags| nags.iter().filter_map(|it| Nag::parse(it)).collect())
+    }
+
     /** 
      * # Arguments
      * * `after_move` is zero based.
@@ -44,8 +55,17 @@ pub trait ChessGame {
      * * `after_variation_move` is zero based
      */
     fn get_after_variation_comment(&self, after_move: Int, after_variation_move: Int) -> Option<&String>;
+
+    /**
+     * The commands, like `[%clk 0:05:00]`, extracted from the comment of a move.
+     * # Arguments
+     * * `after_move` is zero based.
+     */
+    fn get_commands(&self, after_move: Int) -> Option<&MoveCommands>;
 }
 
+#[derive(Debug,PartialEq,Clone,Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ChessGameImpl {
     tags: IndexMap<String,String>,
     moves: Vec<String>,
@@ -54,6 +74,7 @@ pub struct ChessGameImpl {
     after_variations_comments: HashMap<Int,HashMap<Int,String>>,
     game_result: String,
     nags: HashMap<Int,Vec<String>>,
+    commands: HashMap<Int,MoveCommands>,
 }
 
 impl ChessGameImpl {
@@ -66,7 +87,90 @@ impl ChessGameImpl {
            game_result: String,
            nags: HashMap<Int,Vec<String>>) -> ChessGameImpl {
         ChessGameImpl{tags: tags, moves: moves, comments: comments, variations: variations, 
-            after_variations_comments: after_variations_comments, game_result: game_result, nags: nags}
+            after_variations_comments: after_variations_comments, game_result: game_result, nags: nags,
+            commands: HashMap::new()}
+    }
+
+    pub fn with_commands(mut self, commands: HashMap<Int,MoveCommands>) -> ChessGameImpl {
+        self.commands = commands;
+        self
+    }
+
+}
+
+/**
+ * Builds a game, for example to write it or to serialize it. Move indices are zero based.
+ */
+pub struct ChessGameBuilder {
+    game: ChessGameImpl,
+}
+
+impl ChessGameBuilder {
+
+    pub fn new() -> Self {
+        ChessGameBuilder{game: ChessGameImpl::default()}
+    }
+
+    pub fn tag(&mut self, key: &str, value: &str) {
+        self.game.tags.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn add_move(&mut self, mv: &str) {
+        self.game.moves.push(mv.to_string());
+    }
+
+    pub fn before_moves_comment(&mut self, comment: &str) {
+        self.game.comments.insert(-1, comment.to_string());
+    }
+
+    pub fn comment(&mut self, after_move: Int, comment: &str) {
+        self.game.comments.insert(after_move, comment.to_string());
+    }
+
+    /**
+     * Adds a variation, in PGN format, like `2. Nc3 Nf6 {a comment}`.
+     */
+    pub fn add_variation(&mut self, after_move: Int, variation: &str) {
+        self.game.variations.entry(after_move).or_default().push(variation.to_string());
+    }
+
+    pub fn after_variation_comment(&mut self, after_move: Int, after_variation_move: Int, comment: &str) {
+        self.game.after_variations_comments.entry(after_move).or_default()
+            .insert(after_variation_move, comment.to_string());
+    }
+
+    pub fn add_nag(&mut self, after_move: Int, nag: Nag) {
+        self.add_nag_code(after_move, &nag.code().to_string());
+    }
+
+    /**
+     * Adds a NAG by its code, without the `$`, also if it's not a standard one.
+     */
+    pub fn add_nag_code(&mut self, after_move: Int, code: &str) {
+        self.game.nags.entry(after_move).or_default().push(code.to_string());
+    }
+
+    pub fn commands(&mut self, after_move: Int, commands: MoveCommands) {
+        self.game.commands.insert(after_move, commands);
+    }
+
+    /**
+     * Sets the result written after the moves, the `Result` tag must be set separately.
+     */
+    pub fn game_result(&mut self, result: &str) {
+        self.game.game_result = result.to_string();
+    }
+
+    pub fn build(&self) -> ChessGameImpl {
+        self.game.clone()
+    }
+
+}
+
+impl Default for ChessGameBuilder {
+
+    fn default() -> Self {
+        ChessGameBuilder::new()
     }
 
 }
@@ -132,4 +236,8 @@ impl ChessGame for ChessGameImpl {
             _ => None
         }
     }
+
+    fn get_commands(&self, after_move: Int) -> Option<&MoveCommands> {
+        self.commands.get(&after_move)
+    }
 }
@@ -0,0 +1,197 @@
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Error;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::vec;
+
+use base::game::ChessGameImpl;
+use base::parser::ChessParserIterator;
+
+/**
+ * The position of a game in a PGN file.
+ */
+#[derive(Debug,PartialEq,Clone,Copy)]
+pub struct GameIndexEntry {
+    /**
+     * The offset in bytes from the start of the file.
+     */
+    pub offset: u64,
+    /**
+     * The line where the game starts, one based.
+     */
+    pub line: u64,
+}
+
+/**
+ * An index of the games in a PGN file, it can contain all the games or only the ones matched by a query.
+ * It's saved as a text file with the offset and the line of a game in every line.
+ */
+pub struct GameIndex {
+    entries: Vec<GameIndexEntry>,
+}
+
+const HEADER: &str = "# chessrw game index: offset line";
+
+impl GameIndex {
+
+    pub fn new() -> GameIndex {
+        GameIndex{entries: Vec::new()}
+    }
+
+    /**
+     * Builds the index of the games returned by the iterator, from `ChessParser::parse_seekable` since the offsets of
+     * a compressed file cannot be sought.
+     */
+    pub fn build<R: Read>(iterator: &mut ChessParserIterator<R>) -> GameIndex {
+        let mut index = GameIndex::new();
+
+        while iterator.next_temp() {
+            index.push(iterator.get_game_index_entry());
+        }
+
+        index
+    }
+
+    /**
+     * The path of the index file for a PGN file, that is the PGN path with the `.idx` extension appended.
+     */
+    pub fn sidecar_path(pgn_path: &str) -> String {
+        format!("{}.idx", pgn_path)
+    }
+
+    pub fn push(&mut self, entry: GameIndexEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn get_entries(&self) -> &Vec<GameIndexEntry> {
+        &self.entries
+    }
+
+    /**
+     * # Arguments
+     * * `game` is zero based.
+     */
+    pub fn get(&self, game: usize) -> Option<&GameIndexEntry> {
+        self.entries.get(game)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+
+        writeln!(&mut w, "{}", HEADER)?;
+
+        for entry in &self.entries {
+            writeln!(&mut w, "{} {}", entry.offset, entry.line)?;
+        }
+
+        w.flush()
+    }
+
+    pub fn load(path: &str) -> io::Result<GameIndex> {
+        let reader = BufReader::new(File::open(path)?);
+
+        let mut index = GameIndex::new();
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+
+            let values: Vec<Result<u64,_>> = line.split_whitespace().map(|it| it.parse::<u64>()).collect();
+
+            match values.as_slice() {
+                // the lines are one based
+                [Ok(offset), Ok(line)] if *line > 0 => index.push(GameIndexEntry{offset: *offset, line: *line}),
+                _ => return Err(Error::new(ErrorKind::InvalidData,
+                    format!("Invalid index entry in line {} of {}: {}.", i + 1, path, line)))
+            }
+        }
+
+        Ok(index)
+    }
+
+}
+
+impl Default for GameIndex {
+
+    fn default() -> Self {
+        GameIndex::new()
+    }
+
+}
+
+/**
+ * Parses only the games of an index, seeking the file to every game.
+ */
+pub struct IndexedParserIterator {
+    iterator: ChessParserIterator<File>,
+    entries: vec::IntoIter<GameIndexEntry>,
+}
+
+impl IndexedParserIterator {
+
+    pub fn new(iterator: ChessParserIterator<File>, entries: Vec<GameIndexEntry>) -> Self {
+        IndexedParserIterator{iterator, entries: entries.into_iter()}
+    }
+
+    /**
+     * Parses the next game of the index, then it can be accessed with `game()`.
+     */
+    pub fn next_temp(&mut self) -> bool {
+        for entry in self.entries.by_ref() {
+            if let Err(e) = self.iterator.seek_game(&entry) {
+                self.iterator.errors.push(format!("Error in line {}: cannot seek to offset {}: {}.", entry.line,
+                    entry.offset, e));
+                continue;
+            }
+
+            // only the game of the entry must be parsed
+            self.iterator.set_end_offset(Some(entry.offset));
+
+            if self.iterator.next_temp() {
+                return true;
+            }
+        }
+        self.iterator.finish_progress();
+        false
+    }
+
+    /**
+     * The last parsed game.
+     */
+    pub fn game(&self) -> &ChessParserIterator<File> {
+        &self.iterator
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        self.iterator.get_errors()
+    }
+
+}
+
+impl Iterator for IndexedParserIterator {
+    type Item = ChessGameImpl;
+
+    fn next(&mut self) -> Option<ChessGameImpl> {
+        if self.next_temp() {
+            Some(self.iterator.to_game())
+        } else {
+            None
+        }
+    }
+}
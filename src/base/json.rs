@@ -0,0 +1,275 @@
+use std::io::{BufRead, BufReader, Read};
+
+use indexmap::IndexMap;
+use serde_json;
+
+use base::annotation::MoveCommands;
+use base::fen::FEN_PARSER;
+use base::game::{ChessGame, ChessGameBuilder, ChessGameImpl};
+use base::parser::{ChessParser, ChessParserBuilder, ChessParserImpl};
+use base::position::{ChessColor, ChessPosition, other_color};
+use base::tag::Tag;
+use base::writer::comment_with_commands;
+
+lazy_static! {
+    static ref VARIATION_PARSER: ChessParserImpl = {
+        ChessParserBuilder::new().build()
+    };
+}
+
+/**
+ * The JSON form of a game. The variations are trees of moves, instead of PGN text.
+ */
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone,Default)]
+pub struct JsonGame {
+    pub tags: IndexMap<String,String>,
+    /**
+     * The comment before the moves.
+     */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commands: Option<MoveCommands>,
+    pub moves: Vec<JsonMove>,
+    pub result: String,
+}
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone,Default)]
+pub struct JsonMove {
+    pub san: String,
+    /**
+     * The NAG codes, without the `$`.
+     */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commands: Option<MoveCommands>,
+    /**
+     * The alternatives to this move.
+     */
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variations: Vec<JsonVariation>,
+}
+
+#[derive(Serialize,Deserialize,Debug,PartialEq,Clone,Default)]
+pub struct JsonVariation {
+    /**
+     * The comment before the first move of the variation.
+     */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commands: Option<MoveCommands>,
+    pub moves: Vec<JsonMove>,
+    /**
+     * The comment after the end of the variation.
+     */
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_comment: Option<String>,
+}
+
+impl JsonGame {
+
+    pub fn from_game(game: &dyn ChessGame) -> JsonGame {
+        JsonGame{tags: game.get_tags().clone(), comment: game.get_before_moves_comment().cloned(),
+            commands: game.get_commands(-1).cloned(), moves: json_moves(game), result: game.get_game_result().clone()}
+    }
+
+    /**
+     * # Returns
+     * an error if the FEN tag is not valid, it's needed to number the moves of the variations.
+     */
+    pub fn to_game(&self) -> Result<ChessGameImpl, String> {
+        let position = match self.tags.get(&Tag::FEN.to_string()) {
+            Some(fen) => FEN_PARSER.parse(fen)?,
+            None => ChessPosition::initial_position()
+        };
+
+        let mut builder = ChessGameBuilder::new();
+
+        for (key, value) in &self.tags {
+            builder.tag(key, value);
+        }
+        if let Some(ref comment) = self.comment {
+            builder.before_moves_comment(comment);
+        }
+        if let Some(ref commands) = self.commands {
+            builder.commands(-1, commands.clone());
+        }
+
+        let mut color = position.active_color;
+        let mut number = position.full_move_number;
+
+        for (i, mv) in self.moves.iter().enumerate() {
+            let m = i as i16;
+
+            builder.add_move(&mv.san);
+            for nag in &mv.nags {
+                builder.add_nag_code(m, nag);
+            }
+            if let Some(ref comment) = mv.comment {
+                builder.comment(m, comment);
+            }
+            if let Some(ref commands) = mv.commands {
+                builder.commands(m, commands.clone());
+            }
+            for (v, variation) in mv.variations.iter().enumerate() {
+                builder.add_variation(m, &format_variation(variation, color, number));
+                if let Some(ref comment) = variation.after_comment {
+                    builder.after_variation_comment(m, v as i16, comment);
+                }
+            }
+
+            if color == ChessColor::Black {
+                number += 1;
+            }
+            color = other_color(color);
+        }
+
+        builder.game_result(&self.result);
+
+        Ok(builder.build())
+    }
+
+}
+
+fn json_moves(game: &dyn ChessGame) -> Vec<JsonMove> {
+    game.get_moves().iter().enumerate().map(|(i, san)| {
+        let m = i as i16;
+        let variations = game.get_variations(m).map_or_else(Vec::new, |variations| variations.iter().enumerate()
+            .map(|(v, text)| parse_variation(text, game.get_after_variation_comment(m, v as i16).cloned()))
+            .collect());
+
+        JsonMove{san: san.clone(), nags: game.get_nags(m).cloned().unwrap_or_default(),
+            comment: game.get_comment(m).cloned(), commands: game.get_commands(m).cloned(), variations}
+    }).collect()
+}
+
+/**
+ * Parses the PGN text of a variation, its nested variations are parsed recursively.
+ */
+fn parse_variation(text: &str, after_comment: Option<String>) -> JsonVariation {
+    // an empty line would end the variation, the result ends its last move
+    let text = format!("{} *", text.replace('\n', " "));
+    let mut iterator = VARIATION_PARSER.parse_string(&text);
+
+    if iterator.next_temp() {
+        JsonVariation{comment: iterator.get_before_moves_comment().cloned(), commands: iterator.get_commands(-1).cloned(),
+            moves: json_moves(&iterator), after_comment}
+    } else {
+        JsonVariation{after_comment, ..JsonVariation::default()}
+    }
+}
+
+/**
+ * Writes a variation as PGN text, its first move is played by `color`.
+ */
+fn format_variation(variation: &JsonVariation, color: ChessColor, number: u16) -> String {
+    let mut tokens = Vec::new();
+
+    if let Some(comment) = comment_with_commands(variation.comment.as_ref(), variation.commands.as_ref()) {
+        tokens.push(format!("{{{}}}", comment));
+    }
+
+    let mut color = color;
+    let mut number = number;
+    let mut show_number = true;
+
+    for mv in &variation.moves {
+        if color == ChessColor::White {
+            tokens.push(format!("{}. {}", number, mv.san));
+        } else if show_number {
+            tokens.push(format!("{}... {}", number, mv.san));
+        } else {
+            tokens.push(mv.san.clone());
+        }
+        show_number = false;
+
+        for nag in &mv.nags {
+            tokens.push(format!("${}", nag));
+        }
+        if let Some(comment) = comment_with_commands(mv.comment.as_ref(), mv.commands.as_ref()) {
+            tokens.push(format!("{{{}}}", comment));
+            show_number = true;
+        }
+        for nested in &mv.variations {
+            tokens.push(format!("({})", format_variation(nested, color, number)));
+            if let Some(ref comment) = nested.after_comment {
+                tokens.push(format!("{{{}}}", comment));
+            }
+            show_number = true;
+        }
+
+        if color == ChessColor::Black {
+            number += 1;
+        }
+        color = other_color(color);
+    }
+
+    tokens.join(" ")
+}
+
+/**
+ * Reads a JSON array of games.
+ */
+pub fn read_json<R: Read>(reader: R) -> Result<Vec<ChessGameImpl>, String> {
+    let games: Vec<JsonGame> = serde_json::from_reader(reader).map_err(|e| format!("Invalid JSON: {}.", e))?;
+    games.iter().map(|it| it.to_game()).collect()
+}
+
+/**
+ * Reads games in NDJSON format, one JSON game for every line. Invalid lines are skipped and their errors recorded.
+ */
+pub struct NdjsonIterator<R: Read> {
+    reader: BufReader<R>,
+    buf: String,
+    line: u64,
+    errors: Vec<String>,
+}
+
+impl <R: Read> NdjsonIterator<R> {
+
+    pub fn new(reader: R) -> Self {
+        NdjsonIterator{reader: BufReader::new(reader), buf: String::new(), line: 0, errors: Vec::new()}
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+
+}
+
+impl <R: Read> Iterator for NdjsonIterator<R> {
+    type Item = ChessGameImpl;
+
+    fn next(&mut self) -> Option<ChessGameImpl> {
+        loop {
+            self.buf.clear();
+            self.line += 1;
+
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(_) => {
+                    self.errors.push(format!("Error in line {}: cannot read line.", self.line));
+                    continue;
+                }
+            }
+
+            if self.buf.trim().is_empty() {
+                continue;
+            }
+
+            let game = serde_json::from_str::<JsonGame>(&self.buf).map_err(|e| e.to_string())
+                .and_then(|it| it.to_game());
+
+            match game {
+                Ok(game) => return Some(game),
+                Err(e) => self.errors.push(format!("Error in line {}: {}.", self.line, e.trim_end_matches('.')))
+            }
+        }
+    }
+
+}
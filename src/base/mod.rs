@@ -3,4 +3,17 @@ pub mod tag;
 pub mod writer;
 pub mod fen;
 pub mod position;
-pub mod game;
\ No newline at end of file
+pub mod game;
+pub mod compression;
+pub mod borrowed;
+pub mod parallel;
+pub mod index;
+pub mod encoding;
+pub mod annotation;
+pub mod nag;
+pub mod filter;
+pub mod progress;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod notation;
+pub mod descriptive;
\ No newline at end of file
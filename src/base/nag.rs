@@ -0,0 +1,238 @@
+use std::fmt;
+
+macro_rules! nags {
+    ($($code:expr => $name:ident, $description:expr, $symbol:expr;)*) => {
+        /**
+         * The standard Numeric Annotation Glyphs, from 0 to 139.
+         */
+        #[derive(Debug,PartialEq,Eq,Clone,Copy)]
+        pub enum Nag {
+            $($name,)*
+        }
+
+        impl Nag {
+
+            pub fn from_code(code: u8) -> Option<Nag> {
+                match code {
+                    $($code => Some(Nag::$name),)*
+                    _ => None
+                }
+            }
+
+            pub fn code(self) -> u8 {
+                match self {
+                    $(Nag::$name => $code,)*
+                }
+            }
+
+            pub fn description(self) -> &'static str {
+                match self {
+                    $(Nag::$name => $description,)*
+                }
+            }
+
+            /**
+             * The symbol used in printed games, if any, for example "!" or "+-". Some NAGs of white and black share
+             * the same symbol.
+             */
+            pub fn symbol(self) -> Option<&'static str> {
+                match self {
+                    $(Nag::$name => $symbol,)*
+                }
+            }
+
+        }
+    }
+}
+
+nags! {
+    0 => Null, "null annotation", None;
+    1 => GoodMove, "good move", Some("!");
+    2 => PoorMove, "poor move", Some("?");
+    3 => VeryGoodMove, "very good move", Some("!!");
+    4 => VeryPoorMove, "very poor move", Some("??");
+    5 => SpeculativeMove, "speculative move", Some("!?");
+    6 => QuestionableMove, "questionable move", Some("?!");
+    7 => ForcedMove, "forced move (all others lose quickly)", Some("□");
+    8 => SingularMove, "singular move (no reasonable alternatives)", None;
+    9 => WorstMove, "worst move", None;
+    10 => DrawishPosition, "drawish position", Some("=");
+    11 => EqualChancesQuietPosition, "equal chances, quiet position", None;
+    12 => EqualChancesActivePosition, "equal chances, active position", None;
+    13 => UnclearPosition, "unclear position", Some("∞");
+    14 => WhiteSlightAdvantage, "White has a slight advantage", Some("⩲");
+    15 => BlackSlightAdvantage, "Black has a slight advantage", Some("⩱");
+    16 => WhiteModerateAdvantage, "White has a moderate advantage", Some("±");
+    17 => BlackModerateAdvantage, "Black has a moderate advantage", Some("∓");
+    18 => WhiteDecisiveAdvantage, "White has a decisive advantage", Some("+-");
+    19 => BlackDecisiveAdvantage, "Black has a decisive advantage", Some("-+");
+    20 => WhiteCrushingAdvantage, "White has a crushing advantage (should resign)", None;
+    21 => BlackCrushingAdvantage, "Black has a crushing advantage (should resign)", None;
+    22 => WhiteZugzwang, "White is in zugzwang", Some("⨀");
+    23 => BlackZugzwang, "Black is in zugzwang", Some("⨀");
+    24 => WhiteSlightSpaceAdvantage, "White has a slight space advantage", None;
+    25 => BlackSlightSpaceAdvantage, "Black has a slight space advantage", None;
+    26 => WhiteModerateSpaceAdvantage, "White has a moderate space advantage", None;
+    27 => BlackModerateSpaceAdvantage, "Black has a moderate space advantage", None;
+    28 => WhiteDecisiveSpaceAdvantage, "White has a decisive space advantage", None;
+    29 => BlackDecisiveSpaceAdvantage, "Black has a decisive space advantage", None;
+    30 => WhiteSlightTimeAdvantage, "White has a slight time (development) advantage", None;
+    31 => BlackSlightTimeAdvantage, "Black has a slight time (development) advantage", None;
+    32 => WhiteModerateTimeAdvantage, "White has a moderate time (development) advantage", Some("⟳");
+    33 => BlackModerateTimeAdvantage, "Black has a moderate time (development) advantage", Some("⟳");
+    34 => WhiteDecisiveTimeAdvantage, "White has a decisive time (development) advantage", None;
+    35 => BlackDecisiveTimeAdvantage, "Black has a decisive time (development) advantage", None;
+    36 => WhiteInitiative, "White has the initiative", Some("→");
+    37 => BlackInitiative, "Black has the initiative", Some("→");
+    38 => WhiteLastingInitiative, "White has a lasting initiative", None;
+    39 => BlackLastingInitiative, "Black has a lasting initiative", None;
+    40 => WhiteAttack, "White has the attack", Some("↑");
+    41 => BlackAttack, "Black has the attack", Some("↑");
+    42 => WhiteInsufficientCompensation, "White has insufficient compensation for material deficit", None;
+    43 => BlackInsufficientCompensation, "Black has insufficient compensation for material deficit", None;
+    44 => WhiteSufficientCompensation, "White has sufficient compensation for material deficit", Some("=/∞");
+    45 => BlackSufficientCompensation, "Black has sufficient compensation for material deficit", Some("=/∞");
+    46 => WhiteMoreThanAdequateCompensation, "White has more than adequate compensation for material deficit", None;
+    47 => BlackMoreThanAdequateCompensation, "Black has more than adequate compensation for material deficit", None;
+    48 => WhiteSlightCenterControl, "White has a slight center control advantage", None;
+    49 => BlackSlightCenterControl, "Black has a slight center control advantage", None;
+    50 => WhiteModerateCenterControl, "White has a moderate center control advantage", None;
+    51 => BlackModerateCenterControl, "Black has a moderate center control advantage", None;
+    52 => WhiteDecisiveCenterControl, "White has a decisive center control advantage", None;
+    53 => BlackDecisiveCenterControl, "Black has a decisive center control advantage", None;
+    54 => WhiteSlightKingsideControl, "White has a slight kingside control advantage", None;
+    55 => BlackSlightKingsideControl, "Black has a slight kingside control advantage", None;
+    56 => WhiteModerateKingsideControl, "White has a moderate kingside control advantage", None;
+    57 => BlackModerateKingsideControl, "Black has a moderate kingside control advantage", None;
+    58 => WhiteDecisiveKingsideControl, "White has a decisive kingside control advantage", None;
+    59 => BlackDecisiveKingsideControl, "Black has a decisive kingside control advantage", None;
+    60 => WhiteSlightQueensideControl, "White has a slight queenside control advantage", None;
+    61 => BlackSlightQueensideControl, "Black has a slight queenside control advantage", None;
+    62 => WhiteModerateQueensideControl, "White has a moderate queenside control advantage", None;
+    63 => BlackModerateQueensideControl, "Black has a moderate queenside control advantage", None;
+    64 => WhiteDecisiveQueensideControl, "White has a decisive queenside control advantage", None;
+    65 => BlackDecisiveQueensideControl, "Black has a decisive queenside control advantage", None;
+    66 => WhiteVulnerableFirstRank, "White has a vulnerable first rank", None;
+    67 => BlackVulnerableFirstRank, "Black has a vulnerable first rank", None;
+    68 => WhiteWellProtectedFirstRank, "White has a well protected first rank", None;
+    69 => BlackWellProtectedFirstRank, "Black has a well protected first rank", None;
+    70 => WhitePoorlyProtectedKing, "White has a poorly protected king", None;
+    71 => BlackPoorlyProtectedKing, "Black has a poorly protected king", None;
+    72 => WhiteWellProtectedKing, "White has a well protected king", None;
+    73 => BlackWellProtectedKing, "Black has a well protected king", None;
+    74 => WhitePoorlyPlacedKing, "White has a poorly placed king", None;
+    75 => BlackPoorlyPlacedKing, "Black has a poorly placed king", None;
+    76 => WhiteWellPlacedKing, "White has a well placed king", None;
+    77 => BlackWellPlacedKing, "Black has a well placed king", None;
+    78 => WhiteVeryWeakPawnStructure, "White has a very weak pawn structure", None;
+    79 => BlackVeryWeakPawnStructure, "Black has a very weak pawn structure", None;
+    80 => WhiteModeratelyWeakPawnStructure, "White has a moderately weak pawn structure", None;
+    81 => BlackModeratelyWeakPawnStructure, "Black has a moderately weak pawn structure", None;
+    82 => WhiteModeratelyStrongPawnStructure, "White has a moderately strong pawn structure", None;
+    83 => BlackModeratelyStrongPawnStructure, "Black has a moderately strong pawn structure", None;
+    84 => WhiteVeryStrongPawnStructure, "White has a very strong pawn structure", None;
+    85 => BlackVeryStrongPawnStructure, "Black has a very strong pawn structure", None;
+    86 => WhitePoorKnightPlacement, "White has poor knight placement", None;
+    87 => BlackPoorKnightPlacement, "Black has poor knight placement", None;
+    88 => WhiteGoodKnightPlacement, "White has good knight placement", None;
+    89 => BlackGoodKnightPlacement, "Black has good knight placement", None;
+    90 => WhitePoorBishopPlacement, "White has poor bishop placement", None;
+    91 => BlackPoorBishopPlacement, "Black has poor bishop placement", None;
+    92 => WhiteGoodBishopPlacement, "White has good bishop placement", None;
+    93 => BlackGoodBishopPlacement, "Black has good bishop placement", None;
+    94 => WhitePoorRookPlacement, "White has poor rook placement", None;
+    95 => BlackPoorRookPlacement, "Black has poor rook placement", None;
+    96 => WhiteGoodRookPlacement, "White has good rook placement", None;
+    97 => BlackGoodRookPlacement, "Black has good rook placement", None;
+    98 => WhitePoorQueenPlacement, "White has poor queen placement", None;
+    99 => BlackPoorQueenPlacement, "Black has poor queen placement", None;
+    100 => WhiteGoodQueenPlacement, "White has good queen placement", None;
+    101 => BlackGoodQueenPlacement, "Black has good queen placement", None;
+    102 => WhitePoorPieceCoordination, "White has poor piece coordination", None;
+    103 => BlackPoorPieceCoordination, "Black has poor piece coordination", None;
+    104 => WhiteGoodPieceCoordination, "White has good piece coordination", None;
+    105 => BlackGoodPieceCoordination, "Black has good piece coordination", None;
+    106 => WhitePlayedOpeningVeryPoorly, "White has played the opening very poorly", None;
+    107 => BlackPlayedOpeningVeryPoorly, "Black has played the opening very poorly", None;
+    108 => WhitePlayedOpeningPoorly, "White has played the opening poorly", None;
+    109 => BlackPlayedOpeningPoorly, "Black has played the opening poorly", None;
+    110 => WhitePlayedOpeningWell, "White has played the opening well", None;
+    111 => BlackPlayedOpeningWell, "Black has played the opening well", None;
+    112 => WhitePlayedOpeningVeryWell, "White has played the opening very well", None;
+    113 => BlackPlayedOpeningVeryWell, "Black has played the opening very well", None;
+    114 => WhitePlayedMiddlegameVeryPoorly, "White has played the middlegame very poorly", None;
+    115 => BlackPlayedMiddlegameVeryPoorly, "Black has played the middlegame very poorly", None;
+    116 => WhitePlayedMiddlegamePoorly, "White has played the middlegame poorly", None;
+    117 => BlackPlayedMiddlegamePoorly, "Black has played the middlegame poorly", None;
+    118 => WhitePlayedMiddlegameWell, "White has played the middlegame well", None;
+    119 => BlackPlayedMiddlegameWell, "Black has played the middlegame well", None;
+    120 => WhitePlayedMiddlegameVeryWell, "White has played the middlegame very well", None;
+    121 => BlackPlayedMiddlegameVeryWell, "Black has played the middlegame very well", None;
+    122 => WhitePlayedEndingVeryPoorly, "White has played the ending very poorly", None;
+    123 => BlackPlayedEndingVeryPoorly, "Black has played the ending very poorly", None;
+    124 => WhitePlayedEndingPoorly, "White has played the ending poorly", None;
+    125 => BlackPlayedEndingPoorly, "Black has played the ending poorly", None;
+    126 => WhitePlayedEndingWell, "White has played the ending well", None;
+    127 => BlackPlayedEndingWell, "Black has played the ending well", None;
+    128 => WhitePlayedEndingVeryWell, "White has played the ending very well", None;
+    129 => BlackPlayedEndingVeryWell, "Black has played the ending very well", None;
+    130 => WhiteSlightCounterplay, "White has slight counterplay", None;
+    131 => BlackSlightCounterplay, "Black has slight counterplay", None;
+    132 => WhiteModerateCounterplay, "White has moderate counterplay", Some("⇆");
+    133 => BlackModerateCounterplay, "Black has moderate counterplay", Some("⇆");
+    134 => WhiteDecisiveCounterplay, "White has decisive counterplay", None;
+    135 => BlackDecisiveCounterplay, "Black has decisive counterplay", None;
+    136 => WhiteModerateTimeControlPressure, "White has moderate time control pressure", None;
+    137 => BlackModerateTimeControlPressure, "Black has moderate time control pressure", None;
+    138 => WhiteSevereTimeControlPressure, "White has severe time control pressure", Some("⨁");
+    139 => BlackSevereTimeControlPressure, "Black has severe time control pressure", Some("⨁");
+}
+
+/**
+ * The traditional move suffix annotations, that are converted to the NAGs from 1 to 6.
+ */
+const SUFFIXES: [(&str, Nag); 6] = [("!!", Nag::VeryGoodMove), ("??", Nag::VeryPoorMove), ("!?", Nag::SpeculativeMove),
+    ("?!", Nag::QuestionableMove), ("!", Nag::GoodMove), ("?", Nag::PoorMove)];
+
+impl Nag {
+
+    /**
+     * Parses a NAG in the `$n` or `n` format, only the standard codes are accepted.
+     */
+    pub fn parse(s: &str) -> Option<Nag> {
+        s.trim_start_matches('$').parse::<u8>().ok().and_then(Nag::from_code)
+    }
+
+    pub fn from_suffix(suffix: &str) -> Option<Nag> {
+        SUFFIXES.iter().find(|it| it.0 == suffix).map(|it| it.1)
+    }
+
+    /**
+     * True for the move assessments written as a suffix of the move, like "!" or "?!".
+     */
+    pub fn is_suffix(self) -> bool {
+        SUFFIXES.iter().any(|it| it.1 == self)
+    }
+
+}
+
+impl fmt::Display for Nag {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${}", self.code())
+    }
+
+}
+
+/**
+ * Splits a move from its suffix annotation, for example "Nf3!?" is split in "Nf3" and `Nag::SpeculativeMove`.
+ * A move without a known suffix is returned unchanged.
+ */
+pub fn split_suffix(mv: &str) -> (&str, Option<Nag>) {
+    let suffix_start = mv.trim_end_matches(['!', '?']).len();
+
+    match Nag::from_suffix(&mv[suffix_start..]) {
+        Some(nag) if suffix_start > 0 => (&mv[..suffix_start], Some(nag)),
+        _ => (mv, None)
+    }
+}
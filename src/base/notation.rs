@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use base::position::{ChessPosition, PieceType, Square, char_to_piece_type};
+use base::descriptive::{DescriptiveLanguage, parse_descriptive};
+
+#[derive(Debug,PartialEq,Eq,Clone,Copy)]
+pub enum Castling {
+    KingSide,
+    QueenSide,
+}
+
+/**
+ * A move applied to a position, with what's needed to write it in any notation.
+ */
+#[derive(Debug,PartialEq,Clone)]
+pub struct ChessMove {
+    pub piece: PieceType,
+    pub from: Square,
+    /**
+     * The destination of the king, for castling.
+     */
+    pub to: Square,
+    pub capture: bool,
+    pub promotion: Option<PieceType>,
+    pub castling: Option<Castling>,
+    /**
+     * The file and/or the rank of the from square needed in SAN to tell apart two pieces, if any.
+     */
+    pub disambiguation: String,
+    pub check: bool,
+    pub checkmate: bool,
+}
+
+/**
+ * The letters of the knight, bishop, rook, queen and king.
+ */
+#[derive(Debug,PartialEq,Eq,Clone,Copy)]
+pub struct PieceLetters([char; 5]);
+
+pub const ENGLISH_LETTERS: PieceLetters = PieceLetters(['N', 'B', 'R', 'Q', 'K']);
+
+/**
+ * The figurines of the white pieces, used for both colors as in printed games.
+ */
+pub const FIGURINES: PieceLetters = PieceLetters(['♘', '♗', '♖', '♕', '♔']);
+
+const LANGUAGES: [(&str, PieceLetters); 8] = [
+    ("de", PieceLetters(['S', 'L', 'T', 'D', 'K'])),
+    ("es", PieceLetters(['C', 'A', 'T', 'D', 'R'])),
+    ("fr", PieceLetters(['C', 'F', 'T', 'D', 'R'])),
+    ("it", PieceLetters(['C', 'A', 'T', 'D', 'R'])),
+    ("nl", PieceLetters(['P', 'L', 'T', 'D', 'K'])),
+    ("pl", PieceLetters(['S', 'G', 'W', 'H', 'K'])),
+    ("pt", PieceLetters(['C', 'B', 'T', 'D', 'R'])),
+    ("sv", PieceLetters(['S', 'L', 'T', 'D', 'K'])),
+];
+
+impl PieceLetters {
+
+    /**
+     * The letters of the knight, bishop, rook, queen and king.
+     */
+    pub fn new(letters: [char; 5]) -> PieceLetters {
+        PieceLetters(letters)
+    }
+
+    /**
+     * The letters of a language, by its ISO 639-1 code: de, en, es, fr, it, nl, pl, pt or sv.
+     */
+    pub fn from_language(language: &str) -> Option<PieceLetters> {
+        if language == "en" {
+            return Some(ENGLISH_LETTERS);
+        }
+        LANGUAGES.iter().find(|&&(code, _)| code == language).map(|&(_, letters)| letters)
+    }
+
+    /**
+     * # Returns
+     * the piece of the letter, `None` if it's not one of these letters.
+     */
+    pub fn piece(&self, letter: char) -> Option<PieceType> {
+        let pieces = [PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King];
+        self.0.iter().position(|it| *it == letter).map(|i| pieces[i])
+    }
+
+    /**
+     * # Returns
+     * the letter of the piece, `None` for a pawn.
+     */
+    pub fn letter(&self, piece: PieceType) -> Option<char> {
+        match piece {
+            PieceType::Knight => Some(self.0[0]),
+            PieceType::Bishop => Some(self.0[1]),
+            PieceType::Rook => Some(self.0[2]),
+            PieceType::Queen => Some(self.0[3]),
+            PieceType::King => Some(self.0[4]),
+            _ => None
+        }
+    }
+
+}
+
+/**
+ * The notations to write the moves.
+ */
+#[derive(Debug,PartialEq,Eq,Clone,Copy)]
+pub enum Notation {
+    /**
+     * Standard algebraic notation, like `Nxf3`.
+     */
+    San,
+    /**
+     * Long algebraic notation, like `e2-e4` or `Ng1xf3`.
+     */
+    Lan,
+    /**
+     * The notation of the UCI engines, like `e2e4` or `e7e8q`.
+     */
+    Uci,
+    /**
+     * The ICCF numeric notation of correspondence chess, like `5254` or `17181` (a promotion to queen).
+     */
+    Iccf,
+    /**
+     * SAN with the figurines of the pieces, like `♘xf3`.
+     */
+    Figurine,
+    /**
+     * SAN with the letters of the pieces of a language, like `Sxf3` in German.
+     */
+    Localized(PieceLetters),
+    /**
+     * The descriptive notation of old books, like `NxB`, it needs the position before the move (see
+     * `descriptive::descriptive_move`).
+     */
+    Descriptive(DescriptiveLanguage),
+}
+
+/**
+ * Parses the name of a notation: san, lan, uci, iccf, figurine, descriptive, descriptive:es or a language code (see
+ * `PieceLetters::from_language`).
+ */
+pub fn notation_from_name(name: &str) -> Result<Notation, String> {
+    match name.to_lowercase().as_ref() {
+        "san" => Ok(Notation::San),
+        "lan" => Ok(Notation::Lan),
+        "uci" => Ok(Notation::Uci),
+        "iccf" => Ok(Notation::Iccf),
+        "figurine" => Ok(Notation::Figurine),
+        "descriptive" | "descriptive:en" => Ok(Notation::Descriptive(DescriptiveLanguage::English)),
+        "descriptive:es" => Ok(Notation::Descriptive(DescriptiveLanguage::Spanish)),
+        language => match PieceLetters::from_language(language) {
+            Some(letters) => Ok(Notation::Localized(letters)),
+            None => Err(format!("Unknown notation {}.", name))
+        }
+    }
+}
+
+impl ChessMove {
+
+    /**
+     * Writes the move in the given notation, the descriptive notation is written as SAN since it needs the position.
+     */
+    pub fn to_notation(&self, notation: Notation) -> String {
+        match notation {
+            Notation::San => self.san(&ENGLISH_LETTERS),
+            Notation::Lan => self.lan(),
+            Notation::Uci => self.uci(),
+            Notation::Iccf => self.iccf(),
+            Notation::Figurine => self.san(&FIGURINES),
+            Notation::Localized(letters) => self.san(&letters),
+            Notation::Descriptive(_) => self.san(&ENGLISH_LETTERS),
+        }
+    }
+
+    fn san(&self, letters: &PieceLetters) -> String {
+        let mut result = match self.castling {
+            Some(castling) => castling_text(castling).to_string(),
+            None => {
+                let mut result = String::new();
+                match letters.letter(self.piece) {
+                    Some(letter) => {
+                        result.push(letter);
+                        result.push_str(&self.disambiguation);
+                    },
+                    // the file of a pawn capture
+                    None if self.capture => result.push(file_char(&self.from)),
+                    None => ()
+                }
+                if self.capture {
+                    result.push('x');
+                }
+                result.push_str(&self.to.to_string());
+                self.push_promotion(&mut result, letters, true);
+                result
+            }
+        };
+        self.push_check(&mut result);
+        result
+    }
+
+    fn lan(&self) -> String {
+        let mut result = match self.castling {
+            Some(castling) => castling_text(castling).to_string(),
+            None => {
+                let mut result = String::new();
+                if let Some(letter) = ENGLISH_LETTERS.letter(self.piece) {
+                    result.push(letter);
+                }
+                result.push_str(&format!("{}{}{}", self.from, if self.capture { 'x' } else { '-' }, self.to));
+                self.push_promotion(&mut result, &ENGLISH_LETTERS, true);
+                result
+            }
+        };
+        self.push_check(&mut result);
+        result
+    }
+
+    fn uci(&self) -> String {
+        let mut result = format!("{}{}", self.from, self.to);
+        self.push_promotion(&mut result, &ENGLISH_LETTERS, false);
+        result.to_lowercase()
+    }
+
+    fn iccf(&self) -> String {
+        let mut result = format!("{}{}{}{}", self.from.file(), self.from.rank(), self.to.file(), self.to.rank());
+        if let Some(promotion) = self.promotion {
+            result.push(match promotion {
+                PieceType::Queen => '1',
+                PieceType::Rook => '2',
+                PieceType::Bishop => '3',
+                _ => '4'
+            });
+        }
+        result
+    }
+
+    fn push_promotion(&self, result: &mut String, letters: &PieceLetters, with_equals: bool) {
+        if let Some(letter) = self.promotion.and_then(|it| letters.letter(it)) {
+            if with_equals {
+                result.push('=');
+            }
+            result.push(letter);
+        }
+    }
+
+    fn push_check(&self, result: &mut String) {
+        if self.checkmate {
+            result.push('#');
+        } else if self.check {
+            result.push('+');
+        }
+    }
+
+}
+
+impl fmt::Display for ChessMove {
+
+    /**
+     * Writes the move in SAN.
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_notation(Notation::San))
+    }
+
+}
+
+/**
+ * The notation of the moves of the input, they are converted to SAN when the game is parsed.
+ */
+#[derive(Debug,PartialEq,Eq,Clone,Copy)]
+pub enum InputNotation {
+    /**
+     * The moves are kept as read.
+     */
+    San,
+    /**
+     * SAN with the letters of the pieces of a language.
+     */
+    Localized(PieceLetters),
+    /**
+     * SAN with figurines, of any color.
+     */
+    Figurine,
+    /**
+     * The moves can be written with their squares, like `e2e4`, `e2-e4` or `Ng1-f3`, they are replayed to convert
+     * them.
+     */
+    Coordinate,
+    /**
+     * The ICCF numeric notation, like `5254`, the moves are replayed to convert them.
+     */
+    Iccf,
+    /**
+     * The descriptive notation of old books, like `P-K4`, the moves are replayed to convert them. The moves of the
+     * variations are kept as read.
+     */
+    Descriptive(DescriptiveLanguage),
+    /**
+     * Figurines, coordinate and ICCF moves are recognized, the language of the piece letters is detected from the first
+     * game that is not in English and used for the rest of the input.
+     */
+    Auto,
+}
+
+/**
+ * Parses the name of an input notation: san, figurine, coordinate, iccf, auto, descriptive, descriptive:es or a
+ * language code (see `PieceLetters::from_language`).
+ */
+pub fn input_notation_from_name(name: &str) -> Result<InputNotation, String> {
+    match name.to_lowercase().as_ref() {
+        "san" => Ok(InputNotation::San),
+        "figurine" => Ok(InputNotation::Figurine),
+        "coordinate" => Ok(InputNotation::Coordinate),
+        "iccf" => Ok(InputNotation::Iccf),
+        "auto" => Ok(InputNotation::Auto),
+        "descriptive" | "descriptive:en" => Ok(InputNotation::Descriptive(DescriptiveLanguage::English)),
+        "descriptive:es" => Ok(InputNotation::Descriptive(DescriptiveLanguage::Spanish)),
+        language => match PieceLetters::from_language(language) {
+            Some(letters) => Ok(InputNotation::Localized(letters)),
+            None => Err(format!("Unknown input notation {}.", name))
+        }
+    }
+}
+
+/**
+ * Converts the moves of a game, and the piece letters of its variations, to SAN. The variations are not replayed,
+ * so their coordinate, ICCF and descriptive moves are kept as read. On error the moves and the variations may be
+ * partly converted, the caller passes copies to keep them as read.
+ *
+ * # Arguments
+ * * `position` is the initial position of the game, needed only for coordinate, ICCF and descriptive moves.
+ * * `detected` are the letters detected in the previous games of the input, for `InputNotation::Auto`.
+ */
+pub(crate) fn moves_to_san(moves: &mut [String], variations: &mut HashMap<i16, Vec<String>>,
+        position: Option<ChessPosition>, notation: InputNotation, detected: &mut Option<PieceLetters>)
+        -> Result<(), String> {
+    let figurines = notation == InputNotation::Figurine || notation == InputNotation::Auto;
+
+    let letters = match notation {
+        InputNotation::Localized(letters) => Some(letters),
+        InputNotation::Auto => {
+            if detected.is_none() {
+                *detected = detect_letters(moves);
+            }
+            *detected
+        },
+        _ => None
+    };
+
+    if figurines || letters.is_some() {
+        for mv in moves.iter_mut() {
+            *mv = translate(mv, letters.as_ref(), figurines);
+        }
+        for text in variations.values_mut().flat_map(|it| it.iter_mut()) {
+            *text = translate_variation(text, letters.as_ref(), figurines);
+        }
+    }
+
+    if let InputNotation::Descriptive(language) = notation {
+        return replay_moves(moves, position, |position, mv| Some(parse_descriptive(position, mv, language)));
+    }
+
+    let coordinates = notation == InputNotation::Coordinate || notation == InputNotation::Auto;
+    let numeric = notation == InputNotation::Iccf || notation == InputNotation::Auto;
+
+    let parse = |mv: &str| {
+        let coordinate = if coordinates { parse_coordinate_move(mv) } else { None };
+        coordinate.or_else(|| if numeric { parse_iccf_move(mv) } else { None })
+    };
+
+    if moves.iter().any(|it| parse(it).is_some()) {
+        return replay_moves(moves, position, |_, mv| parse(mv).map(Ok));
+    }
+
+    Ok(())
+}
+
+/**
+ * Replays the moves to write them in SAN. `parse` gives the squares of a move, `None` if it's already in SAN.
+ */
+fn replay_moves<F>(moves: &mut [String], position: Option<ChessPosition>, parse: F) -> Result<(), String>
+        where F: Fn(&ChessPosition, &str) -> Option<Result<(Square, Square, Option<PieceType>), String>> {
+    let mut position = position.ok_or("invalid initial position")?;
+
+    for (i, mv) in moves.iter_mut().enumerate() {
+        let result = match parse(&position, mv) {
+            Some(Ok((from, to, promotion))) => position.apply_coordinate_move(&from, &to, promotion)
+                .map(|it| it.to_notation(Notation::San)),
+            Some(Err(e)) => Err(e),
+            None => match position.apply_move(mv) {
+                Some(e) => Err(e),
+                None => Ok(mv.clone())
+            }
+        };
+
+        match result {
+            Ok(san) => *mv = san,
+            Err(e) => return Err(format!("cannot convert move {} (n. {}): {}", mv, i + 1, e.trim_end_matches('.')))
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * The letters, among the known languages, of all the pieces of the moves. `None` if the moves are in English.
+ */
+fn detect_letters(moves: &[String]) -> Option<PieceLetters> {
+    let used: Vec<char> = moves.iter().flat_map(|it| it.chars()).filter(|it| it.is_ascii_uppercase() && *it != 'O')
+        .collect();
+
+    if used.iter().all(|it| ENGLISH_LETTERS.piece(*it).is_some()) {
+        return None;
+    }
+
+    LANGUAGES.iter().map(|&(_, letters)| letters).find(|letters| used.iter().all(|it| letters.piece(*it).is_some()))
+}
+
+/**
+ * Replaces the piece letters and the figurines of a move with the English letters. The figurines of the pawns are
+ * removed.
+ */
+fn translate(mv: &str, letters: Option<&PieceLetters>, figurines: bool) -> String {
+    mv.chars().filter_map(|c| {
+        let piece = match figurine_piece(c) {
+            Some(piece) if figurines => Some(piece),
+            _ => letters.and_then(|it| it.piece(c))
+        };
+        match piece {
+            Some(PieceType::Pawn) => None,
+            Some(piece) => ENGLISH_LETTERS.letter(piece),
+            None => Some(c)
+        }
+    }).collect()
+}
+
+/**
+ * Translates the moves of the text of a variation, the comments are kept as they are.
+ */
+fn translate_variation(text: &str, letters: Option<&PieceLetters>, figurines: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut comment = false;
+
+    for c in text.chars() {
+        if comment || c == '{' || c == '}' {
+            comment = c != '}';
+            result.push(c);
+        } else {
+            result.push_str(&translate(&c.to_string(), letters, figurines));
+        }
+    }
+    result
+}
+
+fn figurine_piece(c: char) -> Option<PieceType> {
+    match c {
+        '♔' | '♚' => Some(PieceType::King),
+        '♕' | '♛' => Some(PieceType::Queen),
+        '♖' | '♜' => Some(PieceType::Rook),
+        '♗' | '♝' => Some(PieceType::Bishop),
+        '♘' | '♞' => Some(PieceType::Knight),
+        '♙' | '♟' => Some(PieceType::Pawn),
+        _ => None
+    }
+}
+
+/**
+ * Parses a move written with its squares, like `e2e4`, `e2-e4`, `e4xd5`, `Ng1-f3` or `e7e8q`. A move with a piece
+ * letter is a coordinate move only with `-` or `x`, otherwise it's SAN.
+ */
+fn parse_coordinate_move(mv: &str) -> Option<(Square, Square, Option<PieceType>)> {
+    let mv = mv.trim_end_matches(['+', '#']);
+    let piece = mv.starts_with(['K', 'Q', 'R', 'B', 'N']);
+    let mut chars: Vec<char> = mv.chars().skip(if piece { 1 } else { 0 }).collect();
+
+    let separator = chars.len() > 2 && (chars[2] == '-' || chars[2] == 'x' || chars[2] == ':');
+    if separator {
+        chars.remove(2);
+    } else if piece {
+        return None;
+    }
+
+    if chars.len() < 4 || chars.len() > 6 {
+        return None;
+    }
+
+    let from = Square::from_string(&chars[..2].iter().collect()).ok()?;
+    let to = Square::from_string(&chars[2..4].iter().collect()).ok()?;
+
+    let promotion = match chars[4..] {
+        [] => None,
+        ['=', p] | [p] => match char_to_piece_type(p.to_ascii_uppercase()) {
+            PieceType::None | PieceType::Pawn | PieceType::King => return None,
+            p => Some(p)
+        },
+        _ => return None
+    };
+
+    Some((from, to, promotion))
+}
+
+/**
+ * Parses a move in ICCF numeric notation: the file and the rank of the from and to squares, like `5254`, and the
+ * promotion, 1 for a queen, 2 for a rook, 3 for a bishop and 4 for a knight.
+ */
+pub(crate) fn parse_iccf_move(mv: &str) -> Option<(Square, Square, Option<PieceType>)> {
+    let digits: Vec<u8> = mv.chars().map(|c| c.to_digit(10).map(|it| it as u8)).collect::<Option<_>>()?;
+
+    if digits.len() != 4 && digits.len() != 5 {
+        return None;
+    }
+
+    let from = Square::new(digits[0], digits[1]).ok()?;
+    let to = Square::new(digits[2], digits[3]).ok()?;
+
+    let promotion = match digits.get(4) {
+        None => None,
+        Some(1) => Some(PieceType::Queen),
+        Some(2) => Some(PieceType::Rook),
+        Some(3) => Some(PieceType::Bishop),
+        Some(4) => Some(PieceType::Knight),
+        Some(_) => return None
+    };
+
+    Some((from, to, promotion))
+}
+
+fn castling_text(castling: Castling) -> &'static str {
+    match castling {
+        Castling::KingSide => "O-O",
+        Castling::QueenSide => "O-O-O",
+    }
+}
+
+fn file_char(square: &Square) -> char {
+    (b'a' + square.file() - 1) as char
+}
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+use base::game::ChessGameImpl;
+use base::parser::{ChessParserConfig, parse_chunk};
+
+/**
+ * The minimum size of a chunk, a chunk is ended at the first game starting after this size.
+ */
+const CHUNK_SIZE: usize = 1_000_000;
+
+struct Chunk {
+    text: Vec<u8>,
+    first_line: u64,
+}
+
+/**
+ * Parses a file in parallel. The file is read in chunks, ended at games boundaries, that is a tag line
+ * after an empty line, and every chunk is parsed in its own thread.
+ * Chunks are read and parsed in batches of one chunk for every thread, the games are returned in the order of the file.
+ */
+pub struct ParallelParserIterator<R: Read> {
+    config: Arc<ChessParserConfig>,
+    reader: BufReader<R>,
+    threads: usize,
+    chunk_size: usize,
+    // the first line of the next chunk, already read
+    pending_line: Vec<u8>,
+    line: u64,
+    games: VecDeque<ChessGameImpl>,
+    errors: Vec<String>,
+    ended: bool,
+    bytes: u64,
+    games_count: u64,
+    reported_errors: usize,
+    read_bytes: Option<Arc<AtomicU64>>,
+}
+
+impl <R: Read> ParallelParserIterator<R> {
+
+    /**
+     * # Arguments
+     * * `read_bytes` the bytes read from the underlying reader, if it's not provided the progress is
+     *   tracked on the bytes read from `reader`.
+     */
+    pub fn new(config: Arc<ChessParserConfig>, reader: BufReader<R>, threads: usize,
+            read_bytes: Option<Arc<AtomicU64>>) -> Self {
+        ParallelParserIterator{config, reader, threads: threads.max(1), chunk_size: CHUNK_SIZE,
+            pending_line: Vec::new(), line: 0, games: VecDeque::new(), errors: Vec::new(), ended: false, bytes: 0,
+            games_count: 0, reported_errors: 0, read_bytes}
+    }
+
+    /**
+     * Sets the minimum size of a chunk, the default is 1,000,000 bytes.
+     */
+    pub fn set_chunk_size(&mut self, size: usize) {
+        self.chunk_size = size;
+    }
+
+    pub fn get_errors(&self) -> &Vec<String> {
+        &self.errors
+    }
+
+    fn read_chunk(&mut self) -> Option<Chunk> {
+        let mut text = mem::take(&mut self.pending_line);
+        let first_line = if text.is_empty() { self.line + 1 } else { self.line };
+        let mut last_line_empty = false;
+        let mut line = Vec::new();
+
+        loop {
+            line.clear();
+
+            match self.reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(count) => self.bytes += count as u64,
+                Err(_) => {
+                    self.errors.push(format!("Error in line {}: cannot read line.", self.line + 1));
+                    break;
+                }
+            }
+
+            self.line += 1;
+
+            if text.len() >= self.chunk_size && last_line_empty && line.first() == Some(&b'[') {
+                self.pending_line = line;
+                return Some(Chunk{text, first_line});
+            }
+
+            last_line_empty = line.iter().all(|c| c.is_ascii_whitespace());
+            text.extend_from_slice(&line);
+        }
+
+        self.ended = true;
+
+        if text.is_empty() {
+            None
+        } else {
+            Some(Chunk{text, first_line})
+        }
+    }
+
+    fn parse_batch(&mut self) {
+        let mut chunks = Vec::new();
+
+        while chunks.len() < self.threads && !self.ended {
+            if let Some(chunk) = self.read_chunk() {
+                chunks.push(chunk);
+            }
+        }
+
+        let config = &self.config;
+
+        let results: Vec<(Vec<ChessGameImpl>, Vec<String>)> = thread::scope(|scope| {
+            let handles: Vec<_> = chunks.iter()
+                .map(|chunk| scope.spawn(move || parse_chunk(config, &chunk.text, chunk.first_line)))
+                .collect();
+
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        for (games, errors) in results {
+            self.games_count += games.len() as u64;
+            self.games.extend(games);
+            self.errors.extend(errors);
+        }
+
+        self.report_progress();
+    }
+
+    fn report_progress(&mut self) {
+        if let Some(ref observer) = self.config.progress {
+            let read = match self.read_bytes {
+                Some(ref read_bytes) => read_bytes.load(Ordering::Relaxed),
+                None => self.bytes
+            };
+            observer.bytes_read(read, self.config.file_size);
+            observer.games_parsed(self.games_count);
+            for error in &self.errors[self.reported_errors..] {
+                observer.error(error);
+            }
+            if self.ended {
+                observer.finished();
+            }
+        }
+        self.reported_errors = self.errors.len();
+    }
+
+}
+
+impl <R: Read> Iterator for ParallelParserIterator<R> {
+    type Item = ChessGameImpl;
+
+    fn next(&mut self) -> Option<ChessGameImpl> {
+        loop {
+            if let Some(game) = self.games.pop_front() {
+                return Some(game);
+            }
+
+            if self.ended {
+                return None;
+            }
+
+            self.parse_batch();
+        }
+    }
+}
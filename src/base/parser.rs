@@ -1,39 +1,87 @@
 use std::fs::File;
 use std::io::BufReader;
 use std::io::BufRead;
+use std::io;
 use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
 use std::char;
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use indicatif::ProgressBar;
-use indicatif::ProgressStyle;
 use indexmap::IndexMap;
 
 use base::tag::*;
 use base::fen::*;
 use base::position::*;
 use base::game::*;
-
+use base::compression::{CompressedReader, Compression, detect_compression};
+use base::index::{GameIndex, GameIndexEntry, IndexedParserIterator};
+use base::borrowed::BorrowedParserIterator;
+use base::parallel::ParallelParserIterator;
+use base::encoding::{Encoding, decode, decode_auto};
+use base::annotation::{MoveCommands, extract_commands};
+use base::nag::split_suffix;
+use base::filter::GameFilter;
+use base::progress::ProgressObserver;
+use base::notation::{InputNotation, PieceLetters, moves_to_san, parse_iccf_move};
+use base::descriptive::is_descriptive_suffix;
+#[cfg(feature = "progressbar")]
+use base::progress::ProgressBarObserver;
+
+pub type TagFilterFn = dyn Fn(&IndexMap<String,String>) -> bool + Send + Sync;
+
+/**
+ * The configuration is owned and shared with `Arc` by parsers and iterators, so they can be moved to other threads.
+ */
 #[derive(Clone)]
-pub struct ChessParserConfig<'a> {
-    ignore_comments: bool,
-    ignore_variations: bool,
-    tag_filter: Option<&'a Fn(&IndexMap<String,String>) -> bool>,
+pub struct ChessParserConfig {
+    pub(crate) ignore_comments: bool,
+    pub(crate) ignore_variations: bool,
+    pub(crate) tag_filter: Option<Arc<TagFilterFn>>,
+    pub(crate) game_filter: Option<Arc<dyn GameFilter>>,
     debug: bool,
-    file_size: u64,
-    progress: bool,
+    pub(crate) file_size: u64,
+    pub(crate) progress: Option<Arc<dyn ProgressObserver>>,
+    pub(crate) input_encoding: Option<Encoding>,
+    pub(crate) input_notation: InputNotation,
 }
 
-pub struct ChessParserBuilder<'a> {
-    config: ChessParserConfig<'a>,
+impl ChessParserConfig {
+
+    pub(crate) fn has_filters(&self) -> bool {
+        self.tag_filter.is_some() || self.game_filter.is_some()
+    }
+
+    pub(crate) fn accept_tags(&self, tags: &IndexMap<String,String>) -> bool {
+        self.tag_filter.as_ref().is_none_or(|f| f(tags)) && self.game_filter.as_ref().is_none_or(|f| f.accept_tags(tags))
+    }
+
+    /**
+     * Whether a token of 4 or 5 digits is read as a move in ICCF numeric notation.
+     */
+    pub(crate) fn numeric_moves(&self) -> bool {
+        self.input_notation == InputNotation::Iccf || self.input_notation == InputNotation::Auto
+    }
+
+    pub(crate) fn needs_game(&self) -> bool {
+        self.game_filter.as_ref().is_some_and(|f| f.needs_game())
+    }
+
 }
 
-impl <'a> ChessParserBuilder<'a> {
+pub struct ChessParserBuilder {
+    config: ChessParserConfig,
+}
+
+impl ChessParserBuilder {
 
     pub fn new() -> Self {
         return ChessParserBuilder{config: ChessParserConfig{ignore_comments: false, ignore_variations: false, 
-            tag_filter: None, debug: false, file_size: 0, progress: false}};
+            tag_filter: None, game_filter: None, debug: false, file_size: 0, progress: None, input_encoding: None,
+            input_notation: InputNotation::San}};
     }
 
     pub fn ignore_comments(&mut self) {
@@ -44,8 +92,17 @@ impl <'a> ChessParserBuilder<'a> {
         self.config.ignore_variations = true;
     }
 
-    pub fn tag_filter(&mut self, filter: &'a Fn(&IndexMap<String,String>) -> bool) {
-        self.config.tag_filter = Some(filter);
+    pub fn tag_filter<F>(&mut self, filter: F) where F: Fn(&IndexMap<String,String>) -> bool + Send + Sync + 'static {
+        self.config.tag_filter = Some(Arc::new(filter));
+    }
+
+    /**
+     * Sets a filter on tags, moves, comments or positions, games rejected on their tags are skipped
+     * without parsing their moves. It can be used together with `tag_filter`.
+     * A filter shared with other code can be passed as an `Arc`.
+     */
+    pub fn game_filter<F: GameFilter + 'static>(&mut self, filter: F) {
+        self.config.game_filter = Some(Arc::new(filter));
     }
 
     pub fn debug(&mut self) {
@@ -56,54 +113,270 @@ impl <'a> ChessParserBuilder<'a> {
         self.config.file_size = size;
     }
 
+    /**
+     * Shows the progress in a console progress bar, the file size should be set.
+     */
+    #[cfg(feature = "progressbar")]
     pub fn progress(&mut self, progress: bool) {
-        self.config.progress = progress;
+        if progress {
+            self.progress_observer(ProgressBarObserver::new());
+        } else {
+            self.config.progress = None;
+        }
+    }
+
+    /**
+     * Sets the receiver of the bytes read, the games parsed and the errors. An observer shared with other code
+     * can be passed as an `Arc`.
+     */
+    pub fn progress_observer<O: ProgressObserver + 'static>(&mut self, observer: O) {
+        self.config.progress = Some(Arc::new(observer));
+    }
+
+    /**
+     * Sets the encoding of the input, by default every line is read as UTF-8 if it's valid, otherwise as Windows-1252.
+     */
+    pub fn input_encoding(&mut self, encoding: Encoding) {
+        self.config.input_encoding = Some(encoding);
+    }
+
+    /**
+     * Sets the notation of the moves of the input, they are converted to SAN. By default they are kept as read.
+     * In the variations only the piece letters and figurines are translated, the other moves are kept as read.
+     * The games of `parse_bytes` are not converted, since their moves are borrowed from the input.
+     */
+    pub fn input_notation(&mut self, notation: InputNotation) {
+        self.config.input_notation = notation;
     }
 
     pub fn build(&self) -> ChessParserImpl {
-        return ChessParserImpl::new(&self.config);
+        return ChessParserImpl::new(self.config.clone());
     }
 
 }
 
-pub trait ChessParser<'a> {
+pub trait ChessParser {
 
-    fn parse(&self, file: File) -> ChessParserIterator<File>;
+    /**
+     * Parses a file, gzip, bzip2, xz and zstd compressed files are decompressed on the fly.
+     */
+    fn parse(&self, file: File) -> ChessParserIterator<CompressedReader<File>>;
 
-    fn parse_string(&self, s: &'a String) -> ChessParserIterator<&'a [u8]>;
+    fn parse_string<'s>(&self, s: &'s str) -> ChessParserIterator<&'s [u8]>;
+
+    /**
+     * Parses any reader, for example standard input or a socket. Compressed input is not detected.
+     * It's not available on a `dyn ChessParser`.
+     */
+    fn parse_reader<R: Read>(&self, reader: R) -> ChessParserIterator<R> where Self: Sized;
+
+    /**
+     * Parses an UTF-8 input, for example a memory mapped file (see `borrowed::map_file`),
+     * without copying the content of the games.
+     */
+    fn parse_bytes<'b>(&self, bytes: &'b [u8]) -> Result<BorrowedParserIterator<'b>, String>;
+
+    /**
+     * Parses a file splitting it in chunks at games boundaries, the chunks are parsed using the given number of threads.
+     * The games are returned in the same order of the file.
+     */
+    fn parse_parallel(&self, file: File, threads: usize) -> ParallelParserIterator<CompressedReader<File>>;
+
+    /**
+     * Parses a file whose offsets can be indexed and sought, for example to build a `GameIndex`. Compressed files
+     * are not supported.
+     */
+    fn parse_seekable(&self, file: File) -> Result<ChessParserIterator<File>, String>;
+
+    /**
+     * Parses a file starting from the game of the index entry. Compressed files are not supported.
+     */
+    fn parse_at(&self, file: File, entry: &GameIndexEntry) -> Result<ChessParserIterator<File>, String>;
+
+    /**
+     * Parses only the games of the index. Compressed files are not supported.
+     */
+    fn parse_indexed(&self, file: File, index: &GameIndex) -> Result<IndexedParserIterator, String>;
 
 }
 
-pub struct ChessParserImpl<'a> {
-    config: &'a ChessParserConfig<'a>,
+pub struct ChessParserImpl {
+    config: Arc<ChessParserConfig>,
 }
 
-impl <'a> ChessParser<'a> for ChessParserImpl<'a> {
+impl ChessParser for ChessParserImpl {
 
-    fn parse(&self, file: File) -> ChessParserIterator<File> {
-        let reader = BufReader::new(file);
-        return ChessParserIterator::new(&self.config, reader);
+    fn parse(&self, file: File) -> ChessParserIterator<CompressedReader<File>> {
+        let reader = CompressedReader::new(file);
+        let read_bytes = reader.read_bytes();
+        let mut iterator = ChessParserIterator::new(self.config.clone(), BufReader::new(reader));
+        // the progress is tracked on the compressed bytes, since the file size is the compressed one
+        iterator.read_bytes = Some(read_bytes);
+        return iterator;
     }
 
-    fn parse_string(&self, s: &'a String) -> ChessParserIterator<&'a [u8]> {
+    fn parse_string<'s>(&self, s: &'s str) -> ChessParserIterator<&'s [u8]> {
         let reader = BufReader::new(s.as_bytes());
-        return ChessParserIterator::new(&self.config, reader);
+        return ChessParserIterator::new(self.config.clone(), reader);
+    }
+
+    fn parse_reader<R: Read>(&self, reader: R) -> ChessParserIterator<R> {
+        ChessParserIterator::new(self.config.clone(), BufReader::new(reader))
+    }
+
+    fn parse_bytes<'b>(&self, bytes: &'b [u8]) -> Result<BorrowedParserIterator<'b>, String> {
+        BorrowedParserIterator::from_bytes(self.config.clone(), bytes)
+    }
+
+    fn parse_parallel(&self, file: File, threads: usize) -> ParallelParserIterator<CompressedReader<File>> {
+        let reader = CompressedReader::new(file);
+        let read_bytes = reader.read_bytes();
+        ParallelParserIterator::new(self.config.clone(), BufReader::new(reader), threads, Some(read_bytes))
+    }
+
+    fn parse_seekable(&self, mut file: File) -> Result<ChessParserIterator<File>, String> {
+        let mut header = [0; 6];
+
+        let compression = file.read(&mut header)
+            .map(|count| detect_compression(&header[..count]))
+            .map_err(|e| format!("Cannot read file: {}.", e))?;
+
+        if compression != Compression::None {
+            return Err(format!("Cannot seek in a {} compressed file.", compression));
+        }
+
+        file.seek(SeekFrom::Start(0)).map_err(|e| format!("Cannot seek to the start of the file: {}.", e))?;
+
+        Ok(ChessParserIterator::new(self.config.clone(), BufReader::new(file)))
+    }
+
+    fn parse_at(&self, file: File, entry: &GameIndexEntry) -> Result<ChessParserIterator<File>, String> {
+        let mut iterator = self.parse_seekable(file)?;
+
+        match iterator.seek_game(entry) {
+            Ok(_) => Ok(iterator),
+            Err(e) => Err(format!("Cannot seek to offset {}: {}.", entry.offset, e))
+        }
+    }
+
+    fn parse_indexed(&self, file: File, index: &GameIndex) -> Result<IndexedParserIterator, String> {
+        let iterator = self.parse_seekable(file)?;
+        Ok(IndexedParserIterator::new(iterator, index.get_entries().clone()))
     }
 }
 
-impl <'a> ChessParserImpl<'a> {
+impl ChessParserImpl {
 
-    pub fn new(config: &'a ChessParserConfig<'a>) -> Self {
-        return ChessParserImpl::<'a>{config: config};
+    pub fn new(config: ChessParserConfig) -> Self {
+        return ChessParserImpl{config: Arc::new(config)};
     }
 
 }
 
 type Int = i16;
 
-pub struct ChessParserIterator<'a,R: Read> {
-    config: &'a ChessParserConfig<'a>,
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/**
+ * The chars that end a move without a white space after it.
+ */
+pub(crate) fn is_move_end(c: char) -> bool {
+    c == '{' || c == '(' || c == ')' || c == '$' || c == ';' || c == '<'
+}
+
+/**
+ * Tells if the text is a castling written with zeros, like 0-0 or 0-0-0+, instead of a game result.
+ */
+fn is_zero_castling(text: &str) -> bool {
+    let mv = text.trim_end_matches(['+', '#', '!', '?']);
+    mv == "0-0" || mv == "0-0-0"
+}
+
+/**
+ * Converts the text of a variation to a single line: rest of line comments are converted to comments between
+ * braces, while escape lines and reserved tokens are removed.
+ */
+pub(crate) fn normalize_variation(variation: &str) -> String {
+    let mut result = String::with_capacity(variation.len());
+    // the end of the current comment, escape line or reserved token
+    let mut token_end: Option<char> = None;
+    let mut keep = true;
+    let mut line_start = true;
+    // the white spaces at the start of a rest of line comment are removed
+    let mut line_comment_start = false;
+
+    for c in variation.chars() {
+        if c == '\r' {
+            continue;
+        }
+
+        match token_end {
+            Some(end) if c == end => {
+                token_end = None;
+                if keep {
+                    result.push('}');
+                }
+                if c == '\n' {
+                    result.push(' ');
+                }
+            },
+            Some(_) => if keep && !(line_comment_start && c.is_whitespace()) {
+                result.push(if c == '\n' { ' ' } else { c });
+                line_comment_start = false;
+            },
+            None => match c {
+                '%' if line_start => {
+                    token_end = Some('\n');
+                    keep = false;
+                },
+                '<' => {
+                    token_end = Some('>');
+                    keep = false;
+                },
+                '{' | ';' => {
+                    token_end = Some(if c == '{' { '}' } else { '\n' });
+                    keep = true;
+                    line_comment_start = c == ';';
+                    result.push('{');
+                },
+                '\n' => result.push(' '),
+                _ => result.push(c)
+            }
+        }
+
+        line_start = c == '\n';
+    }
+
+    // a rest of line comment at the end of the input
+    if token_end.is_some() && keep {
+        result.push('}');
+    }
+
+    result.trim_right().to_string()
+}
+
+/**
+ * Parses a chunk of a file, the progress is not tracked since it's up to the caller.
+ *
+ * # Arguments
+ * * `first_line` is the number of the first line of the chunk in the file, it's used for errors.
+ */
+pub(crate) fn parse_chunk(config: &Arc<ChessParserConfig>, chunk: &[u8], first_line: u64) -> (Vec<ChessGameImpl>, Vec<String>) {
+    let mut iterator = ChessParserIterator::new(config.clone(), BufReader::new(chunk));
+    iterator.progress = None;
+    iterator.line = first_line - 1;
+
+    let mut games = Vec::new();
+    while iterator.next_temp() {
+        games.push(iterator.to_game());
+    }
+    (games, iterator.errors)
+}
+
+pub struct ChessParserIterator<R: Read> {
+    config: Arc<ChessParserConfig>,
     file_reader: BufReader<R>,
+    raw_buf: Vec<u8>,
     buf: String,
     moves: Vec<String>,
     curr_move: String,
@@ -116,16 +389,31 @@ pub struct ChessParserIterator<'a,R: Read> {
     variations: HashMap<Int,Vec<String>>,
     after_variations_comments: HashMap<Int,HashMap<Int,String>>,
     comments: HashMap<Int,String>,
+    commands: HashMap<Int,MoveCommands>,
     tag_key: String,
     tag_value: String,
     variation_count: i32,
+    // the end of a comment or of a reserved token in a variation
+    variation_token_end: Option<char>,
     nags: HashMap<Int,Vec<String>>,
     ch: char,
     skip_game: bool,
     bytes: usize,
-    progress_bar: ProgressBar,
-    errors: Vec<String>,
+    progress: Option<Arc<dyn ProgressObserver>>,
+    games: u64,
+    reported_errors: usize,
+    read_bytes: Option<Arc<AtomicU64>>,
+    pub(crate) errors: Vec<String>,
     line: u64,
+    offset: u64,
+    game_offset: u64,
+    game_line: u64,
+    game_start_pending: bool,
+    end_offset: Option<u64>,
+    // the end of the input has been reached
+    eof: bool,
+    // the piece letters detected in the input, with InputNotation::Auto
+    detected_letters: Option<PieceLetters>,
 }
 
 enum GameResultReason {
@@ -153,32 +441,57 @@ fn result_from_pgn(s: &String) -> Result<GameResultReason, ()> {
     }
 }
 
-impl <'a, R: Read> ChessParserIterator<'a,R> {
+impl <R: Read> ChessParserIterator<R> {
 
-    pub fn new(config: &'a ChessParserConfig<'a>, file_reader: BufReader<R>) -> Self {
-        let pb = ProgressBar::new(config.file_size);
-        pb.set_style(ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .progress_chars("#>-"));
-        return ChessParserIterator{config: config, file_reader: file_reader, buf: String::new(), moves: Vec::new(), 
+    pub fn new(config: Arc<ChessParserConfig>, file_reader: BufReader<R>) -> Self {
+        let progress = config.progress.clone();
+        return ChessParserIterator{config: config, file_reader: file_reader, raw_buf: Vec::new(), buf: String::new(), moves: Vec::new(), 
             curr_move: String::new(), status: Status::Headings, last_char: char::from_digit(0, 10).unwrap(),
             not_parsed: String::new(), result_from_moves: String::new(), tags: IndexMap::new(), end_parse: false,
             variations: HashMap::new(), after_variations_comments: HashMap::new(), comments: HashMap::new(),
-            tag_key: String::new(), tag_value: String::new(),  
-            variation_count: 0, nags: HashMap::new(), 
+            commands: HashMap::new(), tag_key: String::new(), tag_value: String::new(),  
+            variation_count: 0, variation_token_end: None, nags: HashMap::new(), 
             ch: char::from_digit(0, 10).unwrap(), skip_game: false, bytes: 0, 
-            progress_bar: pb, errors: Vec::new(), line: 0};
+            progress, games: 0, reported_errors: 0, read_bytes: None, errors: Vec::new(), line: 0,
+            offset: 0, game_offset: 0, game_line: 0, game_start_pending: true, end_offset: None, detected_letters: None,
+            eof: false};
     }
 
     pub fn to_game(&self) -> ChessGameImpl {
         ChessGameImpl::new(self.tags.clone(), self.moves.clone(), self.comments.clone(), self.variations.clone(),
             self.after_variations_comments.clone(), self.result_from_moves.clone(), self.nags.clone())
+            .with_commands(self.commands.clone())
     }
 
     pub fn get_errors(&self) -> &Vec<String> {
         &self.errors
     }
 
+    /**
+     * The offset in bytes, from the start of the (decompressed) input, of the last parsed game.
+     */
+    pub fn get_game_offset(&self) -> u64 {
+        self.game_offset
+    }
+
+    /**
+     * The line, one based, where the last parsed game starts.
+     */
+    pub fn get_game_line(&self) -> u64 {
+        self.game_line
+    }
+
+    pub fn get_game_index_entry(&self) -> GameIndexEntry {
+        GameIndexEntry{offset: self.game_offset, line: self.game_line}
+    }
+
+    /**
+     * The parse is ended before the first game starting after the given offset.
+     */
+    pub fn set_end_offset(&mut self, end_offset: Option<u64>) {
+        self.end_offset = end_offset;
+    }
+
     fn get_game(&mut self) -> bool {
         // no char has been parsed, it's not a game
         if self.status == Status::Ready {
@@ -187,11 +500,12 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
         // it can happens if there's no result, but it's wrong, since PGN format says it's mandatory. 
         // However it may happen in variations
         if self.status == Status::Move && self.not_parsed.len() > 0 {
-            self.moves.push(self.not_parsed.clone());
-            // TODO check if I need a new instance
-            self.not_parsed.clear();
+            self.push_move();
             self.status = Status::Moves;
         // the file is ended just after the result
+        } else if self.status == Status::GameResult && is_zero_castling(&self.not_parsed) {
+            self.push_move();
+            self.status = Status::Moves;
         } else if self.status == Status::GameResult && self.not_parsed.len() > 0 {
             self.result_from_moves = self.not_parsed.clone();
             // TODO check if I need a new instance
@@ -199,28 +513,66 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
             self.status = Status::Moves;
         }
 
+        if self.config.input_notation != InputNotation::San {
+            self.convert_moves();
+        }
+
         true
     }
 
-    fn parse_comments(&mut self, c: char) {
-        if c == '}' {
-            if !self.config.ignore_comments {
-                let last_move_index = self.moves.last_index();
-                let variations = self.variations.get(&last_move_index);
-                if variations.is_some() && variations.unwrap().len() > 0 {
-                    let av_move_comments = match self.after_variations_comments.entry(last_move_index) {
-                        Entry::Occupied(o) => o.into_mut(),
-                        Entry::Vacant(v) => {
-                            v.insert(HashMap::new())
+    /**
+     * Converts the moves to SAN, if they cannot be converted the moves and the variations are kept as read and an
+     * error is recorded.
+     */
+    fn convert_moves(&mut self) {
+        let position = self.initial_position().ok();
+        let mut moves = self.moves.clone();
+        let mut variations = self.variations.clone();
+
+        match moves_to_san(&mut moves, &mut variations, position, self.config.input_notation,
+                &mut self.detected_letters) {
+            Ok(()) => {
+                self.moves = moves;
+                self.variations = variations;
+            },
+            Err(e) => self.errors.push(format!("Error in line {}: {}.", self.game_line, e))
+        }
+    }
+
+    fn end_comment(&mut self) {
+        if !self.config.ignore_comments {
+            let last_move_index = self.moves.last_index();
+            let variations = self.variations.get(&last_move_index);
+            if variations.is_some() && variations.unwrap().len() > 0 {
+                let av_move_comments = match self.after_variations_comments.entry(last_move_index) {
+                    Entry::Occupied(o) => o.into_mut(),
+                    Entry::Vacant(v) => {
+                        v.insert(HashMap::new())
+                    }
+                };
+                av_move_comments.insert(self.variations[&last_move_index].last_index(), self.not_parsed.trim_right().to_string());
+            } else {
+                // the commands are extracted, only the remaining text is kept as comment
+                match extract_commands(self.not_parsed.trim_right()) {
+                    (Some(commands), text) => {
+                        self.commands.insert(last_move_index, commands);
+                        if !text.is_empty() {
+                            self.comments.insert(last_move_index, text);
                         }
-                    };
-                    av_move_comments.insert(self.variations[&last_move_index].last_index(), self.not_parsed.trim_right().to_string());
-                } else {
-                    self.comments.insert(last_move_index, self.not_parsed.trim_right().to_string());
+                    },
+                    (None, text) => {
+                        self.comments.insert(last_move_index, text);
+                    }
                 }
             }
-            self.not_parsed.clear();
-            self.status = Status::Moves;
+        }
+        self.not_parsed.clear();
+        self.status = Status::Moves;
+    }
+
+    fn parse_comments(&mut self, c: char) {
+        if c == '}' {
+            self.end_comment();
         } else if self.config.ignore_comments {
         } else if c == '\n' {
             self.not_parsed.push(' ');
@@ -229,6 +581,18 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
         }
     }
 
+    /**
+     * A comment from a ';' to the end of the line, it's stored like a comment between braces.
+     */
+    fn parse_line_comment(&mut self, c: char) {
+        if c == '\n' {
+            self.end_comment();
+        } else if self.config.ignore_comments || (self.not_parsed.is_empty() && c.is_whitespace()) {
+        } else {
+            self.not_parsed.push(c);
+        }
+    }
+
     fn parse_heading(&mut self, c: char) {
         if c == ']' {
             if !self.tag_key.is_empty() && !self.tag_value.is_empty() {
@@ -249,7 +613,17 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
     }
 
     fn parse_variation(&mut self, c: char) {
-        if c == ')' {
+        if let Some(end) = self.variation_token_end {
+            if c == end {
+                self.variation_token_end = None;
+            }
+        } else if c == '{' {
+            self.variation_token_end = Some('}');
+        } else if c == ';' {
+            self.variation_token_end = Some('\n');
+        } else if c == '<' {
+            self.variation_token_end = Some('>');
+        } else if c == ')' {
             self.variation_count -= 1;
             if self.variation_count < 0 {
                 if !self.config.ignore_variations {
@@ -260,30 +634,67 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                             v.insert(Vec::new())
                         }
                     };
-                    moves_variations.push(self.not_parsed.trim_right().to_string());
+                    moves_variations.push(normalize_variation(&self.not_parsed));
                 }
                 self.not_parsed.clear();
                 self.status = Status::Moves;
                 self.variation_count = 0;
+                return;
             }
         } else if c == '(' {
             self.variation_count += 1;
-            if !self.config.ignore_variations {
-                self.not_parsed.push(c);
-            }
-        } else if self.config.ignore_variations {
-        } else if c == '\n' {
-            self.not_parsed.push(' ');
-        } else {
+        }
+
+        if !self.config.ignore_variations {
             self.not_parsed.push(c);
         }
     }
 
+    /**
+     * Adds the parsed move, its suffix annotation, like "!?", is converted to a NAG. In descriptive notation a word
+     * like "ch" is added to the previous move.
+     */
+    fn push_move(&mut self) {
+        let descriptive = matches!(self.config.input_notation, InputNotation::Descriptive(_));
+
+        let nag = {
+            let (mv, nag) = split_suffix(&self.not_parsed);
+            if !self.config.numeric_moves() && parse_iccf_move(mv).is_some() {
+                // digits are a move only in ICCF numeric notation
+                self.errors.push(format!("Error in line {}: unknown move {}.", self.line, mv));
+                self.not_parsed.clear();
+                return;
+            }
+            match self.moves.last_mut() {
+                Some(last) if descriptive && is_descriptive_suffix(mv) => {
+                    last.push(' ');
+                    last.push_str(mv);
+                },
+                _ => self.moves.push(mv.to_string())
+            }
+            nag
+        };
+        if let Some(nag) = nag {
+            self.add_nag(nag.code().to_string());
+        }
+        self.not_parsed.clear();
+    }
+
+    fn add_nag(&mut self, nag: String) {
+        let last_move_index = self.moves.last_index();
+        let move_nags = match self.nags.entry(last_move_index) {
+            Entry::Occupied(o) => o.into_mut(),
+            Entry::Vacant(v) => {
+                v.insert(Vec::new())
+            }
+        };
+        move_nags.push(nag);
+    }
+
     fn parse_move(&mut self, c: char) {
-        if c.is_whitespace() {
+        if c.is_whitespace() || is_move_end(c) {
             //println!("{}", self.not_parsed.trim_right().to_string());
-            self.moves.push(self.not_parsed.clone());
-            self.not_parsed.clear();
+            self.push_move();
             self.status = Status::Moves;
         } else {
             self.not_parsed.push(c);
@@ -293,16 +704,10 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
     fn parse_numeric_annotation_glyph(&mut self, c: char) {
         if !c.is_digit(10) {
             if self.not_parsed.len() > 0 {
-                let last_move_index = self.moves.last_index();
-                let move_nags = match self.nags.entry(last_move_index) {
-                    Entry::Occupied(o) => o.into_mut(),
-                    Entry::Vacant(v) => {
-                        v.insert(Vec::new())
-                    }
-                };
-                move_nags.push(self.not_parsed.to_string());
+                let nag = self.not_parsed.to_string();
+                self.add_nag(nag);
             }
-            self.not_parsed.push(c);
+            self.not_parsed.clear();
             self.status = Status::Moves;
         } else {
             self.not_parsed.push(c);
@@ -310,6 +715,29 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
     }
 
     pub fn next_temp(&mut self) -> bool {
+        let found = self.parse_next();
+
+        if found {
+            self.games += 1;
+        } else if self.eof && self.end_offset.is_none() {
+            // a game rejected by the filters or the end offset is not the end of the input
+            self.finish_progress();
+        }
+
+        found
+    }
+
+    /**
+     * Reports the last progress and tells the observer that the parsing is finished.
+     */
+    pub(crate) fn finish_progress(&mut self) {
+        if let Some(observer) = self.progress.take() {
+            self.report_progress(observer.as_ref());
+            observer.finished();
+        }
+    }
+
+    fn parse_next(&mut self) -> bool {
         if self.end_parse {
             return false;
         }
@@ -317,37 +745,44 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
         self.clear();
 
         loop {
-            let count = self.file_reader.read_line(&mut self.buf);
+            let line_offset = self.offset;
+            self.raw_buf.clear();
+            let count = self.file_reader.read_until(b'\n', &mut self.raw_buf);
 
             self.line += 1;
             /*if self.line % 100_000 == 0 {
                 println!("{}", self.line);
             }*/
             
-            if count.is_err() {
-                self.errors.push(format!("Error in line {}: cannot read line.", self.line));
-                continue;
-            }
+            let bytes = match count {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    // a truncated or corrupt input fails again on every read, so it's the end of the input
+                    self.errors.push(format!("Error in line {}: cannot read line.", self.line));
+                    self.raw_buf.clear();
+                    self.end_parse = true;
+                    0
+                }
+            };
+
+            self.decode_line(line_offset == 0);
 
-            let bytes = count.unwrap();
+            self.offset += bytes as u64;
 
-            if self.config.progress {
+            if self.progress.is_some() {
                 self.bytes += bytes;
                 if self.bytes > 100_000 {
-                    self.progress_bar.inc(self.bytes as u64);
-                    self.bytes = 0;
+                    self.update_progress();
                 }
             }
 
             if bytes <= 0 {
+                self.eof = true;
                 if self.moves.is_empty() {
-                    if self.config.progress {
-                        self.progress_bar.inc(self.bytes as u64);
-                    }
                     return false;
                 } else {
                     if self.get_game() {
-                        if self.skip_game {
+                        if self.skip_game || !self.accept_game() {
                             return false;
                         } else {
                             if self.config.debug {
@@ -365,10 +800,17 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                     println!("{}", self.status);
                 }
 
+                // an escape line, its data is ignored
+                if line.starts_with('%') && self.status != Status::Comment {
+                    self.buf.clear();
+                    continue;
+                }
+
                 if self.skip_game {
                     if line.trim_right().is_empty() {
                         if self.status == Status::Moves {
-                            self.status = Status::Headings;
+                            // the tags of the skipped game must not be part of the next one
+                            self.clear();
                             self.skip_game = false;
                             if self.config.debug {
                                 println!("Now skip_game is false.", );
@@ -387,6 +829,16 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                         continue;
                     }
 
+                    if self.game_start_pending && !c.is_whitespace() {
+                        if self.end_offset.is_some_and(|end| line_offset > end) {
+                            self.end_parse = true;
+                            return false;
+                        }
+                        self.game_offset = line_offset;
+                        self.game_line = self.line;
+                        self.game_start_pending = false;
+                    }
+
                     self.last_char = self.ch;
                     self.ch = c;
                     if self.status == Status::Ready {
@@ -403,7 +855,7 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                             if self.status == Status::Headings {
                                 self.status = Status::Moves;
                                 
-                                self.skip_game = self.config.tag_filter.map_or_else(|| false, |f| !f(&self.tags));
+                                self.skip_game = !self.config.accept_tags(&self.tags);
                                 
                                 if self.skip_game {
                                     if self.config.debug {
@@ -416,10 +868,15 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                                 // it's the new line after end of moves, so I add the new game and prepare to parse another
 
                                 if self.get_game() {
-                                    if self.config.debug {
-                                        println!("Returning game.", )
+                                    if self.accept_game() {
+                                        if self.config.debug {
+                                            println!("Returning game.", )
+                                        }
+                                        return true;
                                     }
-                                    return true;
+                                    // the game is rejected by the game filter, the next one is parsed
+                                    self.clear();
+                                    continue;
                                 }
                             }
                         }
@@ -430,6 +887,18 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                         continue;
                     }
 
+                    if self.status == Status::LineComment {
+                        self.parse_line_comment(c);
+                        continue;
+                    }
+
+                    if self.status == Status::Reserved {
+                        if c == '>' {
+                            self.status = Status::Moves;
+                        }
+                        continue;
+                    }
+
                     if self.status == Status::Variation {
                         self.parse_variation(c);
                         continue;
@@ -443,7 +912,7 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                         } else if c.is_whitespace() {
                             continue;
                         } else {
-                            self.skip_game = self.config.tag_filter.map_or_else(|| false, |f| !f(&self.tags));
+                            self.skip_game = !self.config.accept_tags(&self.tags);
 
                             self.status = Status::Moves;
 
@@ -461,8 +930,16 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                         continue;
                     }
 
+                    if self.status == Status::HeadingValueEscape {
+                        self.not_parsed.push(c);
+                        self.status = Status::HeadingValue;
+                        continue;
+                    }
+
                     if self.status == Status::HeadingValue {
-                        if c == '"' {
+                        if c == '\\' {
+                            self.status = Status::HeadingValueEscape;
+                        } else if c == '"' {
                             self.tag_value.push_str(&self.not_parsed);
                             self.status = Status::Heading;
                             self.not_parsed.clear();
@@ -478,21 +955,39 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                         } else if c == '-' || c == '*' {
                             self.status = Status::GameResult;
                             self.not_parsed.push(c);
+                        } else if c.is_whitespace() || is_move_end(c) {
+                            // a move in ICCF numeric notation, otherwise a move number without dots
+                            if parse_iccf_move(self.not_parsed.trim_end_matches(['!', '?'])).is_some() {
+                                self.push_move();
+                            } else {
+                                self.not_parsed.clear();
+                            }
+                            self.status = Status::Moves;
                         } else {
                             self.not_parsed.push(c);
                         }
-                        continue;
+                        // the char ending the move must be parsed as part of the moves
+                        if self.status != Status::Moves || c.is_whitespace() {
+                            continue;
+                        }
                     }
                     
                     if self.status == Status::GameResult {
-                        if c.is_whitespace() {
+                        if (c.is_whitespace() || is_move_end(c)) && is_zero_castling(&self.not_parsed) {
+                            // castling written with zeros, it's a move
+                            self.push_move();
+                            self.status = Status::Moves;
+                        } else if c.is_whitespace() {
                             self.result_from_moves = self.not_parsed.clone();
                             self.not_parsed.clear();
                             self.status = Status::Moves;
                         } else {
                             self.not_parsed.push(c);
                         }
-                        continue;
+                        // the char ending the move must be parsed as part of the moves
+                        if self.status != Status::Moves || c.is_whitespace() {
+                            continue;
+                        }
                     }
 
                     if self.status == Status::MoveNumber {
@@ -506,12 +1001,18 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
 
                     if self.status == Status::Move {
                         self.parse_move(c);
-                        continue;
+                        // the char ending the move must be parsed as part of the moves
+                        if self.status == Status::Move || c.is_whitespace() {
+                            continue;
+                        }
                     }
 
                     if self.status == Status::NumericAnnotationGlyph {
                         self.parse_numeric_annotation_glyph(c);
-                        continue;
+                        // the char after the glyph must be parsed as part of the moves
+                        if self.status == Status::NumericAnnotationGlyph {
+                            continue;
+                        }
                     }
 
                     if self.status == Status::Moves {
@@ -521,6 +1022,12 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
                             self.status = Status::Variation;
                         } else if c == '$' {
                             self.status = Status::NumericAnnotationGlyph;
+                        } else if c == ';' {
+                            self.status = Status::LineComment;
+                        } else if c == '<' {
+                            self.status = Status::Reserved;
+                        } else if c == ')' {
+                            // a parenthesis without its variation
                         } else if c == '*' {
                             self.status = Status::GameResult;
                             self.not_parsed.push(c);
@@ -541,6 +1048,63 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
 
     }
 
+    /**
+     * Checks the parsed game with the game filter, if it needs the whole game.
+     */
+    fn accept_game(&mut self) -> bool {
+        let filter = match self.config.game_filter {
+            Some(ref f) if f.needs_game() => f.clone(),
+            _ => return true
+        };
+
+        let result = filter.accept_game(self);
+
+        result.unwrap_or_else(|e| {
+            self.errors.push(format!("Error in line {}: {}.", self.game_line, e));
+            false
+        })
+    }
+
+    /**
+     * Decodes the raw line appending it to `buf`.
+     */
+    fn decode_line(&mut self, first_line: bool) {
+        let mut raw: &[u8] = &self.raw_buf;
+
+        if first_line && raw.starts_with(UTF8_BOM) {
+            raw = &raw[UTF8_BOM.len()..];
+        }
+
+        match self.config.input_encoding {
+            Some(encoding) => if !decode(raw, encoding, &mut self.buf) {
+                self.errors.push(format!("Error in line {}: invalid {} text.", self.line, encoding));
+            },
+            None => {
+                decode_auto(raw, &mut self.buf);
+            }
+        }
+    }
+
+    fn update_progress(&mut self) {
+        if let Some(observer) = self.progress.clone() {
+            self.report_progress(observer.as_ref());
+        }
+        self.bytes = 0;
+    }
+
+    fn report_progress(&mut self, observer: &dyn ProgressObserver) {
+        let read = match self.read_bytes {
+            Some(ref read_bytes) => read_bytes.load(Ordering::Relaxed),
+            None => self.offset
+        };
+        observer.bytes_read(read, self.config.file_size);
+        observer.games_parsed(self.games);
+        for error in &self.errors[self.reported_errors..] {
+            observer.error(error);
+        }
+        self.reported_errors = self.errors.len();
+    }
+
     pub fn size(mut self) -> u64 {
         let mut count: u64 = 0;
         while self.next_temp() {
@@ -560,11 +1124,30 @@ impl <'a, R: Read> ChessParserIterator<'a,R> {
         self.variations.clear();
         self.after_variations_comments.clear();
         self.comments.clear();
+        self.commands.clear();
         self.tag_key.clear();
         self.tag_value.clear();
         self.variation_count= 0;
+        self.variation_token_end = None;
         self.nags.clear();
         self.last_char = char::from_digit(0, 10).unwrap();
+        self.game_start_pending = true;
+    }
+
+}
+
+impl <R: Read + Seek> ChessParserIterator<R> {
+
+    /**
+     * Moves to the game of the index entry, so it will be the next one to be parsed.
+     */
+    pub fn seek_game(&mut self, entry: &GameIndexEntry) -> io::Result<()> {
+        self.file_reader.seek(SeekFrom::Start(entry.offset))?;
+        self.offset = entry.offset;
+        self.line = entry.line.saturating_sub(1);
+        self.end_parse = false;
+        self.eof = false;
+        Ok(())
     }
 
 }
@@ -594,9 +1177,12 @@ enum Status {
         Headings,
         Heading,
         HeadingValue,
+        HeadingValueEscape, // the char after a backslash in a tag value
         Moves,
         Variation,
         Comment,
+        LineComment, // a comment from a ';' to the end of the line
+        Reserved, // a token between '<' and '>', reserved for future expansion
         MoveUnknown, // can be a move number or a game result  
         MoveNumber, 
         GameResult,
@@ -605,7 +1191,7 @@ enum Status {
         Ready, // no char has been parsed
     }
 
-impl <'a,R: Read> Iterator for ChessParserIterator<'a,R> {
+impl <R: Read> Iterator for ChessParserIterator<R> {
     type Item = ChessGameImpl;
 
     fn next(&mut self) -> Option<ChessGameImpl> {
@@ -617,7 +1203,7 @@ impl <'a,R: Read> Iterator for ChessParserIterator<'a,R> {
     }
 }
 
-impl <'a,R: Read> ChessGame for ChessParserIterator<'a,R> {
+impl <R: Read> ChessGame for ChessParserIterator<R> {
 
     fn get_tags(&self) -> &IndexMap<String,String> {
         &self.tags
@@ -678,4 +1264,8 @@ impl <'a,R: Read> ChessGame for ChessParserIterator<'a,R> {
             _ => None
         }
     }
+
+    fn get_commands(&self, after_move: Int) -> Option<&MoveCommands> {
+        self.commands.get(&after_move)
+    }
 }
\ No newline at end of file
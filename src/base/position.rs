@@ -2,7 +2,15 @@ use std::fmt::Display;
 use std::ops::Add;
 use std::fmt;
 
+use base::notation::{ChessMove, Castling};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+#[cfg(feature = "serde")]
+use serde::de::Error;
+
 #[derive(Display,Debug,PartialEq,Clone,Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Piece {
     None,
     WhitePawn,
@@ -85,6 +93,7 @@ fn piece_type_to_piece(piece_type: PieceType, color: ChessColor) -> Piece {
 }
 
 #[derive(Display,Debug,PartialEq,Clone,Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PieceType {
     None,
     Pawn,
@@ -120,6 +129,7 @@ pub fn piece_type_to_char(piece_type: PieceType) -> char {
 }
 
 #[derive(PartialEq,Clone,Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ChessBoard {
     /**
      * first index is the 8 - rank, the second is file - 1
@@ -387,6 +397,24 @@ impl fmt::Debug for Square {
     }
 }
 
+/**
+ * A square is serialized as its name, like "e4".
+ */
+#[cfg(feature = "serde")]
+impl Serialize for Square {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl <'de> Deserialize<'de> for Square {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Square, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Square::from_string(&name).map_err(D::Error::custom)
+    }
+}
+
 impl Square {
 
     pub fn new(file: u8, rank: u8) -> Result<Square,String> {
@@ -399,19 +427,31 @@ impl Square {
     }
 
     pub fn from_string(square: &String) -> Result<Square,String> {
-        if square.len() != 2 {
-            return Err(format!("Invalid square {}.", square));
-        }
+        let mut chars = square.chars();
 
-        let file_char = square.chars().next().unwrap();
-        let rank_char = square.chars().skip(1).next().unwrap();
+        let (file_char, rank_char) = match (chars.next(), chars.next(), chars.next()) {
+            (Some(file_char), Some(rank_char), None) if file_char.is_ascii() && rank_char.is_ascii() =>
+                (file_char, rank_char),
+            _ => return Err(format!("Invalid square {}.", square))
+        };
 
-        let rank = rank_char as u8 - '0' as u8;
-        let file = file_char as u8 - 'a' as u8  + 1;
+        let rank = (rank_char as u8).wrapping_sub('0' as u8);
+        let file = (file_char as u8).wrapping_sub('a' as u8).wrapping_add(1);
 
         Square::new(file, rank)
     }
 
+    /**
+     * The file, from 1 (a) to 8 (h).
+     */
+    pub fn file(&self) -> u8 {
+        self.file
+    }
+
+    pub fn rank(&self) -> u8 {
+        self.rank
+    }
+
     pub fn north(&self) -> Result<Square,String> {
         self.mv(0, 1)
     }
@@ -443,6 +483,7 @@ const INITIAL_POSITION: ChessPosition =
     board: INITIAL_BOARD, en_passant_target_square: None};
 
 #[derive(PartialEq,Clone,Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ChessPosition {
     pub active_color: ChessColor,
     pub half_move_clock: u16,
@@ -460,16 +501,35 @@ impl ChessPosition {
         INITIAL_POSITION
     }
 
+    /**
+     * Applies a move in SAN, faster than `apply_san_move` since the move is not checked to be legal.
+     * # Returns
+     * the error, if the move cannot be applied.
+     */
     pub fn apply_move(&mut self, san_move: &str) -> Option<String> {
-        let capture = san_move.contains("x");
+        self.apply_san(san_move, false).err()
+    }
 
-        let check = san_move.contains("+");
+    /**
+     * Applies a move in SAN, an illegal move is not applied.
+     * # Returns
+     * the move applied, with its squares, to write it in other notations.
+     */
+    pub fn apply_san_move(&mut self, san_move: &str) -> Result<ChessMove, String> {
+        self.apply_san(san_move, true)
+    }
 
-        let check_mate = san_move.contains("#");
+    /**
+     * Applies a move in SAN, if `detailed` the move is checked to be legal and the move returned has the minimal
+     * disambiguation, the check and the mate.
+     */
+    fn apply_san(&mut self, san_move: &str, detailed: bool) -> Result<ChessMove, String> {
+        let capture = san_move.contains("x");
 
         let mut mv = String::new();
         mv.push_str(san_move);
 
+        mv = mv.replace("e.p.", "");
         mv = mv.replace('x', "");
         mv = mv.replace('+', "");
         mv = mv.replace('?', "");
@@ -477,12 +537,30 @@ impl ChessPosition {
         mv = mv.replace('#', "");
         mv = mv.replace('=', "");
 
-        if &mv == "O-O" || &mv == "0-0" {
-            self.king_side_castling()
+        let color = self.active_color;
+        let king_rank = if color == ChessColor::White { 1 } else { 8 };
+
+        let mut chess_move = if &mv == "O-O" || &mv == "0-0" {
+            if detailed && !self.castling_moves().contains(&(Square{file: 5, rank: king_rank}, Square{file: 7, rank: king_rank})) {
+                return Err(format!("move {}, castling is not allowed", san_move));
+            }
+            self.king_side_castling();
+            ChessMove{piece: PieceType::King, from: Square{file: 5, rank: king_rank}, to: Square{file: 7, rank: king_rank},
+                capture: false, promotion: None, castling: Some(Castling::KingSide), disambiguation: String::new(),
+                check: false, checkmate: false}
         } else if &mv == "O-O-O" || &mv == "0-0-0" {
-            self.queen_side_castling()
+            if detailed && !self.castling_moves().contains(&(Square{file: 5, rank: king_rank}, Square{file: 3, rank: king_rank})) {
+                return Err(format!("move {}, castling is not allowed", san_move));
+            }
+            self.queen_side_castling();
+            ChessMove{piece: PieceType::King, from: Square{file: 5, rank: king_rank}, to: Square{file: 3, rank: king_rank},
+                capture: false, promotion: None, castling: Some(Castling::QueenSide), disambiguation: String::new(),
+                check: false, checkmate: false}
         } else {
-            let first = mv.chars().next().unwrap();
+            let first = match mv.chars().next() {
+                Some(first) => first,
+                None => return Err(format!("move {}, empty move", san_move))
+            };
 
             let mut promotion = None;
 
@@ -493,19 +571,28 @@ impl ChessPosition {
                 PieceType::Pawn
             };
             
-            let last : char = mv.chars().skip(mv.len() -1).take(1).next().unwrap();
+            let last = match mv.chars().last() {
+                Some(last) => last,
+                None => return Err(format!("move {}, missing destination square", san_move))
+            };
 
             if !last.is_digit(10) {
-                promotion = Some(char_to_piece_type(last));
-                mv = mv.chars().take(mv.len() -1).collect();
+                promotion = match char_to_piece_type(last.to_ascii_uppercase()) {
+                    PieceType::Knight | PieceType::Bishop | PieceType::Rook | PieceType::Queen =>
+                        Some(char_to_piece_type(last.to_ascii_uppercase())),
+                    _ => return Err(format!("move {}, invalid promotion {}", san_move, last))
+                };
+                mv.pop();
             }
 
+            let length = mv.chars().count();
+
             // disambiguation: the from square has been specified 
-            if mv.len() == 4 {
+            let (from, to, disambiguation, captured) = if length == 4 {
                 let from_result = Square::from_string(&mv.chars().take(2).collect());
 
                 if from_result.is_err() {
-                    return Some(from_result.unwrap_err());
+                    return Err(from_result.unwrap_err());
                 }
 
                 let from = from_result.unwrap();
@@ -513,29 +600,39 @@ impl ChessPosition {
                 let to_result = Square::from_string(&mv.chars().skip(2).take(2).collect());
 
                 if to_result.is_err() {
-                    return Some(to_result.unwrap_err());
+                    return Err(to_result.unwrap_err());
                 }
 
                 let to = to_result.unwrap();
+                let piece = piece_type_to_piece(piece_type, self.active_color);
+
+                if self.board.get_piece(from.file, from.rank) != piece || detailed && !self.is_legal_move(&from, &to) {
+                    return Err(format!("move {}, illegal move", san_move));
+                }
+
+                let disambiguation = if detailed { self.disambiguation(&from, &to, piece) } else { String::new() };
+                let captured = self.captures(&to, piece_type);
 
-                self.move_piece(from.file, from.rank, to.file, to.rank);
+                if let Some(e) = self.do_move(&from, &to, piece_type, capture, promotion) {
+                    return Err(format!("move {}, error applying move: {}", san_move, e));
+                }
 
-                // TODO promotion
+                (from, to, disambiguation, captured)
             } else {
                 let mut from_file : Option<u8> = None;
                 let mut from_rank : Option<u8> = None;
 
-                if mv.len() == 3 {
+                if length == 3 {
                     let first = mv.chars().next().unwrap();
 
                     if first.is_digit(10) {
                         if first < '1' || first > '8' {
-                            return Some(format!("move {}, invalid rank {}", san_move, first))
+                            return Err(format!("move {}, invalid rank {}", san_move, first))
                         }
                         from_rank = Some(first as u8 - '0' as u8);
                     } else {
                         if first < 'a' || first > 'h' {
-                            return Some(format!("move {}, invalid file {}", san_move, first))
+                            return Err(format!("move {}, invalid file {}", san_move, first))
                         }
                         from_file = Some(first as u8 - 'a' as u8 + 1);
                     }
@@ -546,7 +643,7 @@ impl ChessPosition {
                 let to_result = Square::from_string(&mv);
 
                 if to_result.is_err() {
-                    return Some(to_result.unwrap_err());
+                    return Err(to_result.unwrap_err());
                 }
 
                 let to = to_result.unwrap();
@@ -609,12 +706,12 @@ impl ChessPosition {
                                 let do_move_result = self.do_move(&from, &to, piece_type, capture, promotion);
 
                                 if do_move_result.is_some() {
-                                    return Some(format!("move {}, error applying move: {}", san_move, do_move_result.unwrap()));
+                                    return Err(format!("move {}, error applying move: {}", san_move, do_move_result.unwrap()));
                                 }
 
                                 if !self.king_in_check(self.active_color) {
                                     if from_square.is_some() {
-                                        return Some(format!("move {}, cannot disambiguate move during check test", san_move));
+                                        return Err(format!("move {}, cannot disambiguate move during check test", san_move));
                                     }
                                     from_square = Some(*from)
                                 }
@@ -625,44 +722,336 @@ impl ChessPosition {
                             if from_square.is_some() {
                                 from_square.unwrap()
                             } else {
-                                return Some(format!("move {}, cannot disambiguate move, multiple from squares: {:?}", san_move, &from_squares));
+                                return Err(format!("move {}, cannot disambiguate move, multiple from squares: {:?}", san_move, &from_squares));
                             }
                         }
                     }
                 };
 
+                if detailed && !self.is_legal_move(&from, &to) {
+                    return Err(format!("move {}, illegal move", san_move));
+                }
+
+                let disambiguation = if detailed { self.disambiguation(&from, &to, piece) } else { String::new() };
+                let captured = self.captures(&to, piece_type);
                 self.do_move(&from, &to, piece_type, capture, promotion);
 
+                (from, to, disambiguation, captured)
+            };
+
+            ChessMove{piece: piece_type, from, to, capture: capture || captured, promotion, castling: None, disambiguation,
+                check: false, checkmate: false}
+        };
+
+        self.next_move();
+
+        if detailed {
+            chess_move.check = self.has_king(self.active_color) && self.king_in_check(self.active_color);
+            chess_move.checkmate = chess_move.check && self.legal_moves().is_empty();
+        }
+
+        Ok(chess_move)
+    }
+
+    /**
+     * Applies a move given with its squares, like `e2e4` in UCI. A pawn reaching the last rank without a promotion
+     * is promoted to queen.
+     * # Returns
+     * the move applied, to write it in SAN.
+     */
+    pub fn apply_coordinate_move(&mut self, from: &Square, to: &Square, promotion: Option<PieceType>)
+            -> Result<ChessMove, String> {
+        let piece = self.board.get_piece(from.file, from.rank);
+
+        if piece == Piece::None || piece_color(&piece) != Some(self.active_color) {
+            return Err(format!("no piece to move in {}", from));
+        }
+
+        let piece_type = char_to_piece_type(piece_to_char(piece).to_ascii_uppercase());
+
+        if piece_type == PieceType::King && from.file == 5 && (to.file == 7 || to.file == 3) && from.rank == to.rank {
+            return self.apply_san_move(if to.file == 7 { "O-O" } else { "O-O-O" });
+        }
+
+        let mut san = String::new();
+
+        if piece_type == PieceType::Pawn {
+            if from.file != to.file {
+                san.push((b'a' + from.file - 1) as char);
+                san.push('x');
+            }
+        } else {
+            san.push(piece_type_to_char(piece_type));
+            san.push_str(&self.disambiguation(from, to, piece));
+            if self.captures(to, piece_type) {
+                san.push('x');
             }
         }
 
-        self.next_move();
-                
-        None
+        san.push_str(&to.to_string());
+
+        let last_rank = if self.active_color == ChessColor::White { 8 } else { 1 };
+        if piece_type == PieceType::Pawn && to.rank == last_rank {
+            san.push('=');
+            san.push(piece_type_to_char(promotion.unwrap_or(PieceType::Queen)));
+        }
+
+        self.apply_san_move(&san)
+    }
+
+    /**
+     * The legal moves of the active color, as from and to squares. Castling is a move of the king by two squares and
+     * a promotion is listed once, whatever the promoted piece.
+     */
+    pub fn legal_moves(&self) -> Vec<(Square, Square)> {
+        self.legal_moves_where(|_, _| true)
+    }
+
+    /**
+     * The legal moves accepted by the filter, faster than filtering `legal_moves` since only the accepted moves are
+     * checked to not leave the king in check.
+     */
+    pub fn legal_moves_where<F: Fn(&Square, &Square) -> bool>(&self, filter: F) -> Vec<(Square, Square)> {
+        let color = self.active_color;
+        let mut moves = Vec::new();
+
+        for file in 1..9 {
+            for rank in 1..9 {
+                let piece = self.board.get_piece(file, rank);
+                if piece_color(&piece) != Some(color) {
+                    continue;
+                }
+
+                let from = Square{file, rank};
+                for to in self.targets(&from, piece) {
+                    if filter(&from, &to) && self.keeps_king_safe(&from, &to) {
+                        moves.push((from, to));
+                    }
+                }
+            }
+        }
+
+        moves.extend(self.castling_moves().into_iter().filter(|(from, to)| filter(from, to)));
+        moves
+    }
+
+    /**
+     * Whether a piece of the active color can move from a square to another, castling is a move of the king by two
+     * squares.
+     */
+    pub fn is_legal_move(&self, from: &Square, to: &Square) -> bool {
+        let piece = self.board.get_piece(from.file, from.rank);
+
+        piece_color(&piece) == Some(self.active_color) &&
+            (self.targets(from, piece).contains(to) && self.keeps_king_safe(from, to) ||
+                self.castling_moves().contains(&(*from, *to)))
+    }
+
+    /**
+     * Whether the king of the active color is in check and has no legal move.
+     */
+    pub fn is_checkmate(&self) -> bool {
+        self.has_king(self.active_color) && self.king_in_check(self.active_color) && self.legal_moves().is_empty()
+    }
+
+    /**
+     * Whether the active color has no legal move without being in check.
+     */
+    pub fn is_stalemate(&self) -> bool {
+        self.has_king(self.active_color) && !self.king_in_check(self.active_color) && self.legal_moves().is_empty()
+    }
+
+    /**
+     * The squares a piece can move to, without checking if its king is left in check.
+     */
+    fn targets(&self, from: &Square, piece: Piece) -> Vec<Square> {
+        let mut targets = match char_to_piece_type(piece_to_char(piece).to_ascii_uppercase()) {
+            PieceType::Pawn => self.pawn_targets(from),
+            PieceType::Knight => ChessBoard::reachable_from_knight(from),
+            PieceType::Bishop => self.board.reachable_from_sliding_piece(from, true, false),
+            PieceType::Rook => self.board.reachable_from_sliding_piece(from, false, true),
+            PieceType::Queen => self.board.reachable_from_sliding_piece(from, true, true),
+            PieceType::King => ChessBoard::reachable_from_king(from),
+            PieceType::None => vec![]
+        };
+
+        targets.retain(|to| piece_color(&self.board.get_piece(to.file, to.rank)) != piece_color(&piece));
+        targets
+    }
+
+    fn keeps_king_safe(&self, from: &Square, to: &Square) -> bool {
+        let color = self.active_color;
+        let piece = self.board.get_piece(from.file, from.rank);
+
+        let mut position = *self;
+        position.move_piece(from.file, from.rank, to.file, to.rank);
+        if piece == piece_type_to_piece(PieceType::Pawn, color) && self.en_passant_target_square == Some(*to) {
+            position.board.set_piece(to.file, from.rank, Piece::None);
+        }
+
+        !position.has_king(color) || !position.king_in_check(color)
+    }
+
+    /**
+     * The squares a pawn can move to, without checking if its king is left in check.
+     */
+    fn pawn_targets(&self, from: &Square) -> Vec<Square> {
+        let (direction, start_rank) = if self.active_color == ChessColor::White { (1, 2) } else { (-1, 7) };
+        let mut targets = Vec::new();
+
+        if let Ok(one) = from.mv(0, direction) {
+            if self.board.get_piece(one.file, one.rank) == Piece::None {
+                targets.push(one);
+                if from.rank == start_rank {
+                    let two = from.mv(0, 2 * direction).unwrap();
+                    if self.board.get_piece(two.file, two.rank) == Piece::None {
+                        targets.push(two);
+                    }
+                }
+            }
+        }
+
+        for file_offset in &[-1, 1] {
+            if let Ok(to) = from.mv(*file_offset, direction) {
+                let captured = self.board.get_piece(to.file, to.rank);
+                if piece_color(&captured) == Some(other_color(self.active_color)) ||
+                        self.en_passant_target_square == Some(to) {
+                    targets.push(to);
+                }
+            }
+        }
+
+        targets
+    }
 
+    fn castling_moves(&self) -> Vec<(Square, Square)> {
+        let color = self.active_color;
+        let rank = if color == ChessColor::White { 1 } else { 8 };
+        let king = Square{file: 5, rank};
+        let (king_side, queen_side) = if color == ChessColor::White {
+            (self.white_king_side_castling, self.white_queen_side_castling)
+        } else {
+            (self.black_king_side_castling, self.black_queen_side_castling)
+        };
+
+        let mut moves = Vec::new();
+
+        if self.board.get_piece(5, rank) != piece_type_to_piece(PieceType::King, color) || self.king_in_check(color) {
+            return moves;
+        }
+
+        let rook = piece_type_to_piece(PieceType::Rook, color);
+        let empty = |files: &[u8]| files.iter().all(|&file| self.board.get_piece(file, rank) == Piece::None);
+        let safe = |files: &[u8]| files.iter().all(|&file| self.board.is_safe(&Square{file, rank}, other_color(color)));
+
+        if king_side && self.board.get_piece(8, rank) == rook && empty(&[6, 7]) && safe(&[6, 7]) {
+            moves.push((king, Square{file: 7, rank}));
+        }
+        if queen_side && self.board.get_piece(1, rank) == rook && empty(&[2, 3, 4]) && safe(&[3, 4]) {
+            moves.push((king, Square{file: 3, rank}));
+        }
+
+        moves
+    }
+
+    /**
+     * The file, the rank or both of the from square, if other pieces like the moved one can go to the same square.
+     */
+    fn disambiguation(&self, from: &Square, to: &Square, piece: Piece) -> String {
+        let piece_type = char_to_piece_type(piece_to_char(piece).to_ascii_uppercase());
+        let to_piece = self.board.get_piece(to.file, to.rank);
+        let capture = to_piece != Piece::None;
+
+        let reachable = match piece_type {
+            PieceType::Knight => ChessBoard::reachable_from_knight(to),
+            PieceType::Bishop => self.board.reachable_from_sliding_piece(to, true, false),
+            PieceType::Rook => self.board.reachable_from_sliding_piece(to, false, true),
+            PieceType::Queen => self.board.reachable_from_sliding_piece(to, true, true),
+            _ => return String::new()
+        };
+
+        let others: Vec<Square> = self.board.find_piece(piece).into_iter()
+            .filter(|it| it != from && reachable.contains(it) && self.valid_move(it, to, &piece, &to_piece, capture))
+            .filter(|it| {
+                // a pinned piece cannot move
+                let mut position = *self;
+                position.move_piece(it.file, it.rank, to.file, to.rank);
+                !position.has_king(self.active_color) || !position.king_in_check(self.active_color)
+            })
+            .collect();
+
+        let file = (b'a' + from.file - 1) as char;
+
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|it| it.file != from.file) {
+            file.to_string()
+        } else if others.iter().all(|it| it.rank != from.rank) {
+            from.rank.to_string()
+        } else {
+            format!("{}{}", file, from.rank)
+        }
+    }
+
+    /**
+     * Whether a piece moving to the square captures, also en passant.
+     */
+    fn captures(&self, to: &Square, piece_type: PieceType) -> bool {
+        self.board.get_piece(to.file, to.rank) != Piece::None ||
+            piece_type == PieceType::Pawn && self.en_passant_target_square == Some(*to)
+    }
+
+    fn has_king(&self, color: ChessColor) -> bool {
+        !self.board.find_piece(piece_type_to_piece(PieceType::King, color)).is_empty()
     }
 
     fn queen_side_castling(&mut self) -> () {
         if self.active_color == ChessColor::White {
             self.move_piece(5, 1, 3, 1);
             self.move_piece(1, 1, 4, 1);
-            self.white_queen_side_castling = false;
         } else {
             self.move_piece(5, 8, 3, 8);
             self.move_piece(1, 8, 4, 8);
-            self.black_queen_side_castling = false;
         }
+        self.castled();
     }
 
     fn king_side_castling(&mut self) -> () {
         if self.active_color == ChessColor::White {
             self.move_piece(5, 1, 7, 1);
             self.move_piece(8, 1, 6, 1);
-            self.white_king_side_castling = false;
         } else {
             self.move_piece(5, 8, 7, 8);
             self.move_piece(8, 8, 6, 8);
-            self.black_king_side_castling = false;
+        }
+        self.castled();
+    }
+
+    fn castled(&mut self) {
+        let king_rank = if self.active_color == ChessColor::White { 1 } else { 8 };
+        self.update_castling_rights(&Square{file: 5, rank: king_rank});
+        self.en_passant_target_square = None;
+        self.half_move_clock += 1;
+    }
+
+    /**
+     * Removes the castling rights lost when a piece moves from or to the square of a king or of a rook.
+     */
+    fn update_castling_rights(&mut self, square: &Square) {
+        match (square.file, square.rank) {
+            (5, 1) => {
+                self.white_king_side_castling = false;
+                self.white_queen_side_castling = false;
+            },
+            (5, 8) => {
+                self.black_king_side_castling = false;
+                self.black_queen_side_castling = false;
+            },
+            (1, 1) => self.white_queen_side_castling = false,
+            (8, 1) => self.white_king_side_castling = false,
+            (1, 8) => self.black_queen_side_castling = false,
+            (8, 8) => self.black_king_side_castling = false,
+            _ => ()
         }
     }
 
@@ -679,6 +1068,15 @@ impl ChessPosition {
     }
 
     fn do_move(&mut self, from: &Square, to: &Square, piece_type: PieceType, capture: bool, promotion: Option<PieceType>) -> Option<String> {
+        if piece_type == PieceType::Pawn || capture || self.board.get_piece(to.file, to.rank) != Piece::None {
+            self.half_move_clock = 0;
+        } else {
+            self.half_move_clock += 1;
+        }
+
+        self.update_castling_rights(from);
+        self.update_castling_rights(to);
+
         self.move_piece(from.file, from.rank, to.file, to.rank); // move to board
 
         if capture && piece_type == PieceType::Pawn && 
@@ -750,12 +1148,10 @@ impl ChessPosition {
     fn next_move(&mut self) {
         if self.active_color == ChessColor::White {
             self.active_color = ChessColor::Black;
-            self.full_move_number += 1; // TODO is it correct?
         } else {
             self.active_color = ChessColor::White;
+            self.full_move_number += 1;
         }
-
-        self.half_move_clock += 1;
     }
 
     fn move_piece(&mut self, from_file: u8, from_rank: u8, to_file: u8, to_rank: u8) {
@@ -766,12 +1162,13 @@ impl ChessPosition {
 }
 
 #[derive(Display,Debug,PartialEq,Clone,Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ChessColor {
     White,
     Black
 }
 
-fn other_color(color: ChessColor) -> ChessColor {
+pub fn other_color(color: ChessColor) -> ChessColor {
     if color == ChessColor::White {
         ChessColor::Black
     } else {
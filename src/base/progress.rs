@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+#[cfg(feature = "progressbar")]
+use indicatif::{ProgressBar, ProgressStyle};
+
+/**
+ * Receives the progress of a parser, for example to show it in a GUI. It's shared between threads by the
+ * parallel parser, so it must be `Sync`.
+ */
+pub trait ProgressObserver: Send + Sync {
+
+    /**
+     * Called periodically with the bytes read from the file, they are the compressed bytes for compressed files.
+     * `total` is the size given with `ChessParserBuilder::file_size`, 0 if it's unknown.
+     */
+    fn bytes_read(&self, _read: u64, _total: u64) {
+    }
+
+    /**
+     * Called periodically with the number of games parsed so far, the games rejected by filters are not counted.
+     */
+    fn games_parsed(&self, _count: u64) {
+    }
+
+    /**
+     * Called for every error found, some time after it has been found.
+     */
+    fn error(&self, _error: &str) {
+    }
+
+    /**
+     * Called once, when the input is ended.
+     */
+    fn finished(&self) {
+    }
+
+}
+
+impl <O: ProgressObserver + ?Sized> ProgressObserver for Arc<O> {
+
+    fn bytes_read(&self, read: u64, total: u64) {
+        (**self).bytes_read(read, total)
+    }
+
+    fn games_parsed(&self, count: u64) {
+        (**self).games_parsed(count)
+    }
+
+    fn error(&self, error: &str) {
+        (**self).error(error)
+    }
+
+    fn finished(&self) {
+        (**self).finished()
+    }
+
+}
+
+/**
+ * Shows the progress of the bytes read in a console progress bar.
+ */
+#[cfg(feature = "progressbar")]
+pub struct ProgressBarObserver {
+    bar: ProgressBar,
+}
+
+#[cfg(feature = "progressbar")]
+impl ProgressBarObserver {
+
+    pub fn new() -> Self {
+        let bar = ProgressBar::new(0);
+        bar.set_style(ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+            .progress_chars("#>-"));
+        ProgressBarObserver{bar}
+    }
+
+}
+
+#[cfg(feature = "progressbar")]
+impl Default for ProgressBarObserver {
+
+    fn default() -> Self {
+        ProgressBarObserver::new()
+    }
+
+}
+
+#[cfg(feature = "progressbar")]
+impl ProgressObserver for ProgressBarObserver {
+
+    fn bytes_read(&self, read: u64, total: u64) {
+        self.bar.set_length(total);
+        self.bar.set_position(read);
+    }
+
+    fn finished(&self) {
+        self.bar.finish();
+    }
+
+}
@@ -1,5 +1,6 @@
 #[derive(ToString,Debug)]
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Tag {
         Event, // the name of the tournament or match event.
         Site, // the location of the event. This is in "City, Region COUNTRY" format, where COUNTRY is the three-letter International Olympic Committee code for the country. An example is "New York City, NY USA".
@@ -19,6 +20,27 @@ pub enum Tag {
         ECO // ECO classification
 }
 
+/**
+ * The tags every game must have, in the order they are written.
+ */
+pub const SEVEN_TAG_ROSTER: [Tag; 7] = [Tag::Event, Tag::Site, Tag::Date, Tag::Round, Tag::White, Tag::Black, Tag::Result];
+
+const ALL_TAGS: [Tag; 16] = [Tag::Event, Tag::Site, Tag::Date, Tag::Round, Tag::White, Tag::Black, Tag::Result,
+    Tag::Annotator, Tag::PlyCount, Tag::TimeControl, Tag::Time, Tag::Termination, Tag::Mode, Tag::FEN, Tag::SetUp,
+    Tag::ECO];
+
+impl Tag {
+
+    /**
+     * # Returns
+     * the tag with the given name, `None` if it's not a known tag.
+     */
+    pub fn from_name(name: &str) -> Option<Tag> {
+        ALL_TAGS.iter().find(|it| it.to_string() == name).cloned()
+    }
+
+}
+
 pub fn mandatory_tag(tag: Tag) -> bool {
     return tag == Tag::Event ||
         tag == Tag::Site ||
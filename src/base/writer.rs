@@ -1,14 +1,230 @@
-use std::fs::File;
 use std::io::BufWriter;
 use std::io::Write;
 use std::io::Error;
-use std::io::ErrorKind;
+use std::borrow::Cow;
+use indexmap::IndexMap;
 use base::game::ChessGame;
+use base::annotation::MoveCommands;
+use base::encoding::{Encoding, EncodingWriter};
+use base::nag::Nag;
 use base::position::*;
+use base::fen::to_fen;
+use base::tag::{Tag, SEVEN_TAG_ROSTER, mandatory_tag};
+use base::notation::Notation;
+use base::descriptive::descriptive_move;
+#[cfg(feature = "json")]
+use base::json::JsonGame;
+#[cfg(feature = "json")]
+use serde_json;
+
+#[derive(Display,Debug,PartialEq,Clone,Copy)]
+pub enum OutputFormat {
+    #[strum(serialize="pgn")]
+    Pgn,
+    /**
+     * A JSON array of games, see `JsonGame`.
+     */
+    #[cfg(feature = "json")]
+    #[strum(serialize="json")]
+    Json,
+    /**
+     * A JSON game for every line.
+     */
+    #[cfg(feature = "json")]
+    #[strum(serialize="ndjson")]
+    Ndjson,
+    /**
+     * A row for every game, with the columns set with `ChessWriterBuilder::columns`.
+     */
+    #[strum(serialize="csv")]
+    Csv,
+    #[strum(serialize="tsv")]
+    Tsv,
+}
+
+pub fn output_format_from_name(name: &str) -> Result<OutputFormat, String> {
+    match name.to_lowercase().as_ref() {
+        "pgn" => Ok(OutputFormat::Pgn),
+        #[cfg(feature = "json")]
+        "json" => Ok(OutputFormat::Json),
+        #[cfg(feature = "json")]
+        "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+        "csv" => Ok(OutputFormat::Csv),
+        "tsv" => Ok(OutputFormat::Tsv),
+        _ => Err(format!("Unknown output format {}.", name))
+    }
+}
+
+/**
+ * What the writer does with a move that cannot be applied when it normalizes the moves, see
+ * `ChessWriterBuilder::normalize`.
+ */
+#[derive(Debug,PartialEq,Clone,Copy)]
+pub enum IllegalMoves {
+    /**
+     * The move is followed by an "Illegal move" comment, the next moves are written as read.
+     */
+    Flag,
+    /**
+     * The game is ended before the move and its result is "*".
+     */
+    Truncate,
+}
+
+pub fn illegal_moves_from_name(name: &str) -> Result<IllegalMoves, String> {
+    match name.to_lowercase().as_ref() {
+        "flag" => Ok(IllegalMoves::Flag),
+        "truncate" => Ok(IllegalMoves::Truncate),
+        _ => Err(format!("Unknown illegal moves handling {}.", name))
+    }
+}
+
+const ILLEGAL_MOVE_COMMENT: &str = "{Illegal move}";
+
+const FINAL_FEN_TAG: &str = "FinalFEN";
+
+const ORIGINAL_RESULT_TAG: &str = "OriginalResult";
+
+const OPENING_PLIES: usize = 10;
+
+const MAX_LINE_LENGTH: usize = 80;
+
+/**
+ * A column of the CSV and TSV output, a tag or a value computed from the moves.
+ */
+#[derive(Debug,PartialEq,Clone)]
+pub enum CsvColumn {
+    Tag(String),
+    /**
+     * The number of moves, in plies, of the game, also if the PlyCount tag is missing or wrong.
+     */
+    PlyCount,
+    /**
+     * The FEN of the position at the end of the game, empty if a move cannot be applied.
+     */
+    FinalFen,
+    /**
+     * The given number of first plies, like "1. e4 e5 2. Nf3".
+     */
+    Opening(usize),
+}
+
+impl CsvColumn {
+
+    /**
+     * Parses a column name, the computed columns are `@plycount`, `@finalfen`, `@opening` (the first 10 plies)
+     * and `@opening:N`, any other name is a tag.
+     */
+    pub fn from_name(name: &str) -> Result<CsvColumn, String> {
+        let name = name.trim();
+        let lowercase = name.to_lowercase();
+
+        match lowercase.as_ref() {
+            "" => Err("Empty column name.".to_string()),
+            "@plycount" => Ok(CsvColumn::PlyCount),
+            "@finalfen" => Ok(CsvColumn::FinalFen),
+            "@opening" => Ok(CsvColumn::Opening(OPENING_PLIES)),
+            _ if lowercase.starts_with("@opening:") => match lowercase["@opening:".len()..].parse::<usize>() {
+                Ok(plies) => Ok(CsvColumn::Opening(plies)),
+                Err(_) => Err(format!("Invalid number of plies in column {}.", name))
+            },
+            _ if lowercase.starts_with('@') => Err(format!("Unknown computed column {}.", name)),
+            _ => Ok(CsvColumn::Tag(name.to_string()))
+        }
+    }
+
+    /**
+     * The name written in the header row.
+     */
+    pub fn name(&self) -> String {
+        match *self {
+            CsvColumn::Tag(ref tag) => tag.clone(),
+            CsvColumn::PlyCount => "@plycount".to_string(),
+            CsvColumn::FinalFen => "@finalfen".to_string(),
+            CsvColumn::Opening(plies) if plies == OPENING_PLIES => "@opening".to_string(),
+            CsvColumn::Opening(plies) => format!("@opening:{}", plies),
+        }
+    }
+
+    fn value(&self, game: &dyn ChessGame) -> String {
+        match *self {
+            CsvColumn::Tag(ref tag) => game.get_tags().get(tag).cloned().unwrap_or_default(),
+            CsvColumn::PlyCount => game.get_moves().len().to_string(),
+            CsvColumn::FinalFen => final_position(game, game.get_moves().len()).map(|it| to_fen(&it)).unwrap_or_default(),
+            CsvColumn::Opening(plies) => opening_moves(game, plies),
+        }
+    }
+
+}
+
+/**
+ * The position after the given number of plies, `None` if a move cannot be applied.
+ */
+fn final_position(game: &dyn ChessGame, plies: usize) -> Option<ChessPosition> {
+    let mut position = game.initial_position().ok()?;
+    for mv in game.get_moves().iter().take(plies) {
+        if position.apply_move(mv).is_some() {
+            return None;
+        }
+    }
+    Some(position)
+}
+
+fn opening_moves(game: &dyn ChessGame, plies: usize) -> String {
+    let position = match game.initial_position() {
+        Ok(p) => p,
+        Err(_) => return String::new()
+    };
+
+    let mut color = position.active_color;
+    let mut number = position.full_move_number;
+    let mut tokens = Vec::new();
+
+    for (i, mv) in game.get_moves().iter().take(plies).enumerate() {
+        if color == ChessColor::White {
+            tokens.push(format!("{}. {}", number, mv));
+        } else {
+            tokens.push(if i == 0 { format!("{}... {}", number, mv) } else { mv.clone() });
+            number += 1;
+        }
+        color = other_color(color);
+    }
+
+    tokens.join(" ")
+}
+
+/**
+ * Quotes a CSV field if needed, in TSV tabs and new lines are replaced by spaces.
+ */
+fn csv_field(value: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        value.replace(['\t', '\r', '\n'], " ")
+    } else if value.contains([delimiter, '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
 
 #[derive(Clone)]
 pub struct ChessWriterConfig {
     notags: bool,
+    encoding: Encoding,
+    nag_symbols: bool,
+    format: OutputFormat,
+    columns: Vec<CsvColumn>,
+    canonical_tags: bool,
+    sort_tags: bool,
+    known_tags_only: bool,
+    nonags: bool,
+    nocommands: bool,
+    variation_depth: Option<usize>,
+    annotator: Option<String>,
+    notation: Notation,
+    normalize: Option<IllegalMoves>,
+    enrich: bool,
+    final_fen: bool,
+    termination: bool,
 }
 
 pub struct ChessWriterBuilder{
@@ -18,150 +234,732 @@ pub struct ChessWriterBuilder{
 impl ChessWriterBuilder {
 
     pub fn new() -> ChessWriterBuilder {
-        ChessWriterBuilder{config: ChessWriterConfig{notags: false}}
+        ChessWriterBuilder{config: ChessWriterConfig{notags: false, encoding: Encoding::Utf8, nag_symbols: false,
+            format: OutputFormat::Pgn,
+            columns: ["Event", "Site", "Date", "Round", "White", "Black", "Result"].iter()
+                .map(|it| CsvColumn::Tag(it.to_string())).collect(),
+            canonical_tags: false, sort_tags: false, known_tags_only: false, nonags: false, nocommands: false,
+            variation_depth: None, annotator: None, notation: Notation::San, normalize: None,
+            enrich: false, final_fen: false, termination: false}}
     }
     
-    pub fn build(&self, file: File) -> ChessWriter {
-        ChessWriter::new(self.config.clone(), file)
+    /**
+     * Builds a writer to a file, the standard output or any other sink. To write to a `Vec<u8>` and read it
+     * afterwards pass a `&mut Vec<u8>`.
+     */
+    pub fn build<W: Write>(&self, w: W) -> ChessWriter<W> {
+        ChessWriter::new(self.config.clone(), w)
     }
 
     pub fn notags(&mut self) {
         self.config.notags = true;
     }
 
+    /**
+     * Sets the encoding of the output, the default is UTF-8. Chars that cannot be encoded are written as '?'.
+     */
+    pub fn encoding(&mut self, encoding: Encoding) {
+        self.config.encoding = encoding;
+    }
+
+    /**
+     * The first move assessment of a move, from $1 to $6, is written as its suffix, like "!?", instead of `$n`.
+     * The other NAGs are written as `$n`, since their symbols cannot be read back.
+     */
+    pub fn nag_symbols(&mut self) {
+        self.config.nag_symbols = true;
+    }
+
+    /**
+     * Sets the format of the output, the default is PGN.
+     */
+    pub fn format(&mut self, format: OutputFormat) {
+        self.config.format = format;
+    }
+
+    /**
+     * The tags are written in the PGN export order: first the seven tag roster, the missing tags are written as `?`
+     * (`????.??.??` for the date and the game result for the result), then the other tags. `SetUp "1"` is written
+     * before the FEN tag.
+     */
+    pub fn canonical_tags(&mut self) {
+        self.config.canonical_tags = true;
+    }
+
+    /**
+     * The tags after the seven tag roster are sorted by name, it implies `canonical_tags`.
+     */
+    pub fn sort_tags(&mut self) {
+        self.config.canonical_tags = true;
+        self.config.sort_tags = true;
+    }
+
+    /**
+     * Only the tags of `Tag` are written, it implies `canonical_tags`.
+     */
+    pub fn known_tags_only(&mut self) {
+        self.config.canonical_tags = true;
+        self.config.known_tags_only = true;
+    }
+
+    /**
+     * The NAGs and the move assessments, like "!?", are not written, also in the variations.
+     */
+    pub fn nonags(&mut self) {
+        self.config.nonags = true;
+    }
+
+    /**
+     * The commands of the comments, like `[%clk 0:05:00]`, are not written, while the text of the comments is kept.
+     */
+    pub fn nocommands(&mut self) {
+        self.config.nocommands = true;
+    }
+
+    /**
+     * Only the variations up to the given depth are written: 0 writes no variations, 1 only the variations of the
+     * main line, without the nested ones, and so on.
+     */
+    pub fn variation_depth(&mut self, depth: usize) {
+        self.config.variation_depth = Some(depth);
+    }
+
+    /**
+     * The comments are written only for the games whose Annotator tag contains the given name, ignoring the case.
+     * The commands are not comments, they are kept.
+     */
+    pub fn annotator(&mut self, annotator: &str) {
+        self.config.annotator = Some(annotator.to_lowercase());
+    }
+
+    /**
+     * Sets the notation of the moves of the PGN output, the default is SAN. The moves are replayed to write them in
+     * other notations, from the first move that cannot be applied they are written as read.
+     */
+    pub fn notation(&mut self, notation: Notation) {
+        self.config.notation = notation;
+    }
+
+    /**
+     * Replays the moves to write them normalized: castling as O-O, minimal disambiguation, promotion as e8=Q and
+     * the right check and mate signs. A move that cannot be applied is flagged or ends the game, with result * and
+     * the old Result tag in an OriginalResult tag.
+     */
+    pub fn normalize(&mut self, illegal_moves: IllegalMoves) {
+        self.config.normalize = Some(illegal_moves);
+    }
+
+    /**
+     * Sets the PlyCount tag of the PGN output to the number of moves and makes the Result tag and the result of the
+     * movetext agree: a checkmate or a stalemate at the end of the moves decides the result, otherwise the result of
+     * the movetext wins over the tag. A Result tag with a different value is kept in an OriginalResult tag.
+     */
+    pub fn enrich(&mut self) {
+        self.config.enrich = true;
+    }
+
+    /**
+     * Adds to the PGN output a FinalFEN tag with the position at the end of the moves, if they can be applied.
+     */
+    pub fn final_fen(&mut self) {
+        self.config.final_fen = true;
+    }
+
+    /**
+     * Sets the Termination tag of the PGN output to "normal" when the game ends with a checkmate or a stalemate and
+     * to "unterminated" when its result is "*".
+     */
+    pub fn termination(&mut self) {
+        self.config.termination = true;
+    }
+
+    /**
+     * Sets the columns of the CSV and TSV output, the default is the seven tag roster.
+     */
+    pub fn columns(&mut self, columns: Vec<CsvColumn>) {
+        self.config.columns = columns;
+    }
+
 }
 
-pub struct ChessWriter{
+pub struct ChessWriter<W: Write> {
     config: ChessWriterConfig,
-    w: BufWriter<File>,
+    w: EncodingWriter<BufWriter<W>>,
+    games: u64,
+    finished: bool,
 }
 
-impl ChessWriter {
+impl <W: Write> ChessWriter<W> {
 
-    pub fn new(config: ChessWriterConfig, file: File) -> ChessWriter {
-        ChessWriter{config: config, w: BufWriter::new(file)}
+    pub fn new(config: ChessWriterConfig, w: W) -> ChessWriter<W> {
+        let encoding = config.encoding;
+        ChessWriter{config: config, w: EncodingWriter::new(BufWriter::new(w), encoding), games: 0, finished: false}
     }
 
     pub fn write(&mut self, game: &ChessGame) -> Result<(), Error> {
-        if !self.config.notags {
-            let tags = game.get_tags();
+        match self.config.format {
+            OutputFormat::Pgn => self.write_pgn(game)?,
+            #[cfg(feature = "json")]
+            OutputFormat::Json => {
+                write!(&mut self.w, "{}", if self.games == 0 { "[\n" } else { ",\n" })?;
+                self.write_json(game)?;
+            },
+            #[cfg(feature = "json")]
+            OutputFormat::Ndjson => {
+                self.write_json(game)?;
+                writeln!(&mut self.w)?;
+            },
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                if self.games == 0 {
+                    self.write_csv_header()?;
+                }
+                let row: Vec<String> = self.config.columns.iter().map(|it| it.value(game)).collect();
+                self.write_csv_row(&row)?;
+            }
+        }
+        self.games += 1;
+
+        self.w.flush()
+    }
+
+    /**
+     * Ends the output, that is closes the array of JSON games. It's called when the writer is dropped,
+     * but then errors are lost.
+     */
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        match self.config.format {
+            #[cfg(feature = "json")]
+            OutputFormat::Json => write!(&mut self.w, "{}", if self.games == 0 { "[]\n" } else { "\n]\n" })?,
+            OutputFormat::Csv | OutputFormat::Tsv if self.games == 0 => self.write_csv_header()?,
+            _ => ()
+        }
+
+        self.w.flush()
+    }
+
+    fn write_csv_header(&mut self) -> Result<(), Error> {
+        let names: Vec<String> = self.config.columns.iter().map(|it| it.name()).collect();
+        self.write_csv_row(&names)
+    }
+
+    fn write_csv_row(&mut self, values: &[String]) -> Result<(), Error> {
+        let delimiter = if self.config.format == OutputFormat::Tsv { '\t' } else { ',' };
+        let fields: Vec<String> = values.iter().map(|it| csv_field(it, delimiter)).collect();
+        writeln!(&mut self.w, "{}", fields.join(&delimiter.to_string()))
+    }
+
+    #[cfg(feature = "json")]
+    fn write_json(&mut self, game: &dyn ChessGame) -> Result<(), Error> {
+        let mut json = JsonGame::from_game(game);
+        if self.config.notags {
+            json.tags.clear();
+        }
+        serde_json::to_writer(&mut self.w, &json)?;
+        Ok(())
+    }
+
+    fn write_pgn(&mut self, game: &dyn ChessGame) -> Result<(), Error> {
+        // the movetext is computed first, a game truncated at an illegal move has no result
+        let (mut tokens, truncated) = self.movetext_tokens(game)?;
+        let (tags, result) = self.output_tags(game, truncated);
 
-            if !tags.is_empty() {
+        if !self.config.notags {
+            if self.config.canonical_tags {
+                let tags = self.canonical_tags(&tags, &result);
                 for (tag_key,tag_value) in tags {
-                    write!(&mut self.w, "[{} \"{}\"]\n", tag_key, tag_value)?;
+                    writeln!(&mut self.w, "[{} \"{}\"]", tag_key, escape_tag_value(&tag_value))?;
+                }
+
+                writeln!(&mut self.w)?;
+            } else if !tags.is_empty() {
+                for (tag_key,tag_value) in tags.iter() {
+                    write!(&mut self.w, "[{} \"{}\"]\n", tag_key, escape_tag_value(tag_value))?;
                 }
 
                 write!(&mut self.w, "\n")?;
             }
         }
 
-        match game.get_before_moves_comment() {
-            Some(comment) => write!(&mut self.w, "{}\n", comment)?,
-            None => ()
+        tokens.push(result);
+
+        self.write_wrapped(&tokens)?;
+        writeln!(&mut self.w)
+    }
+
+    /**
+     * The tags and the result to write, they're changed if the moves are truncated or the tags are derived from the
+     * moves, see `ChessWriterBuilder::enrich`.
+     */
+    fn output_tags<'g>(&self, game: &'g dyn ChessGame, truncated: Option<usize>)
+            -> (Cow<'g, IndexMap<String, String>>, String) {
+        let mut result = if truncated.is_some() { "*".to_string() } else { game.get_game_result().clone() };
+
+        if truncated.is_none() && !self.config.enrich && !self.config.final_fen && !self.config.termination {
+            return (Cow::Borrowed(game.get_tags()), result);
+        }
+
+        let mut tags = game.get_tags().clone();
+        let plies = truncated.unwrap_or(game.get_moves().len());
+        let position = if self.config.enrich || self.config.final_fen || self.config.termination {
+            final_position(game, plies)
+        } else {
+            None
         };
+        let checkmate = position.as_ref().is_some_and(|it| it.is_checkmate());
+        let stalemate = position.as_ref().is_some_and(|it| it.is_stalemate());
 
-        // TODO error
-        self.export_moves(game)?;
+        if self.config.enrich {
+            if checkmate {
+                let winner = other_color(position.as_ref().unwrap().active_color);
+                result = if winner == ChessColor::White { "1-0" } else { "0-1" }.to_string();
+            } else if stalemate {
+                result = "1/2-1/2".to_string();
+            } else if result.is_empty() {
+                // the movetext has no result
+                result = tags.get(&Tag::Result.to_string()).cloned().unwrap_or_else(|| "*".to_string());
+            }
+            tags.insert(Tag::PlyCount.to_string(), plies.to_string());
+        }
 
-        write!(&mut self.w, "{}\n\n", game.get_game_result())?;
+        if truncated.is_some() || self.config.enrich {
+            // a different Result tag is kept in the OriginalResult tag, not lost
+            if let Some(old) = tags.insert(Tag::Result.to_string(), result.clone()).filter(|it| *it != result) {
+                tags.insert(ORIGINAL_RESULT_TAG.to_string(), old);
+            }
+        }
 
-        self.w.flush()
+        if let Some(ref position) = position.filter(|_| self.config.final_fen) {
+            tags.insert(FINAL_FEN_TAG.to_string(), to_fen(position));
+        }
+
+        if self.config.termination {
+            if checkmate || stalemate {
+                tags.insert(Tag::Termination.to_string(), "normal".to_string());
+            } else if result == "*" {
+                tags.insert(Tag::Termination.to_string(), "unterminated".to_string());
+            }
+        }
+
+        (Cow::Owned(tags), result)
+    }
+
+    /**
+     * The tags in the PGN export order, see `ChessWriterBuilder::canonical_tags`.
+     */
+    fn canonical_tags(&self, tags: &IndexMap<String, String>, result: &str) -> Vec<(String, String)> {
+        let mut result: Vec<(String, String)> = SEVEN_TAG_ROSTER.iter().map(|tag| {
+            let name = tag.to_string();
+            let value = match tags.get(&name) {
+                Some(value) => value.clone(),
+                None if *tag == Tag::Date => "????.??.??".to_string(),
+                None if *tag == Tag::Result => result.to_string(),
+                None => "?".to_string()
+            };
+            (name, value)
+        }).collect();
+
+        let mut others: Vec<(String, String)> = tags.iter()
+            .filter(|&(name, _)| match Tag::from_name(name) {
+                Some(tag) => !mandatory_tag(tag) && tag != Tag::SetUp,
+                None => !self.config.known_tags_only
+            })
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        if self.config.sort_tags {
+            others.sort();
+        }
+
+        for (name, value) in others {
+            if name == Tag::FEN.to_string() {
+                result.push((Tag::SetUp.to_string(), "1".to_string()));
+            }
+            result.push((name, value));
+        }
+
+        result
     }
 
-    fn export_moves(&mut self, game: &ChessGame) -> Result<(),Error> {
-        //let comment: Option<String> = None;
+    /**
+     * The tokens of the movetext, comments and variations are split at spaces, and the number of plies written if
+     * the moves are truncated at an illegal move.
+     */
+    fn movetext_tokens(&self, game: &dyn ChessGame) -> Result<(Vec<String>, Option<usize>), Error> {
         let position = match game.initial_position() {
             Ok(p) => p,
-            Err(e) => return Result::Err(Error::new(ErrorKind::Other, e))
+            Err(e) => return Err(Error::other(e))
+        };
+
+        let mut tokens = Vec::new();
+
+        let keep_comments = match self.config.annotator {
+            Some(ref annotator) => game.get_tags().get(&Tag::Annotator.to_string())
+                .is_some_and(|it| it.to_lowercase().contains(annotator)),
+            None => true
         };
 
+        if let Some(comment) = self.comment_text(game.get_before_moves_comment(), game.get_commands(-1), keep_comments) {
+            push_words(&mut tokens, &format!("{{{}}}", comment));
+        }
+
         let mut active_color = position.active_color;
-//        let mut half_move_clock = position.half_move_clock;
         let mut full_move_number = position.full_move_number;
+        // the number of a black move is written at the start and after comments and variations
+        let mut show_number = true;
+        // the position is needed only to write the moves in other notations or normalized
+        let mut replayed = if self.config.notation == Notation::San && self.config.normalize.is_none() {
+            None
+        } else {
+            Some(position)
+        };
 
-        if active_color == ChessColor::Black {
-             write!(&mut self.w, "{}... ", full_move_number)?;
-        }
+        for (i, mv) in game.get_moves().iter().enumerate() {
+            let m = i as i16;
+            let before_move = replayed;
+            let mv = self.move_text(mv, &mut replayed);
+            let illegal = before_move.is_some() && replayed.is_none();
 
-        let mut m = -1;
-        for mv in game.get_moves() {
-            m += 1;
-            if (m % 6) == 0 && m > 0 {
-                write!(&mut self.w, "\n")?;
+            if illegal && self.config.normalize == Some(IllegalMoves::Truncate) {
+                return Ok((tokens, Some(i)));
             }
+
             if active_color == ChessColor::White {
-                write!(&mut self.w, "{}. ", full_move_number)?;
+                tokens.push(format!("{}.", full_move_number));
+            } else if show_number {
+                tokens.push(format!("{}...", full_move_number));
             }
+            show_number = false;
 
+            if self.config.nonags {
+                tokens.push(mv);
+            } else {
+                let (suffix, nags) = self.nag_tokens(game.get_nags(m));
 
-/*            Move move = game.getMoves().get(m);
-            SANMove sanMove = new SANMove(game.getGameType(), position, move, "=", false);
-            */
-            write!(&mut self.w, "{} ", mv)?;
+                tokens.push(format!("{}{}", mv, suffix));
+                tokens.extend(nags);
+            }
 
-            match game.get_nags(m) {
-                Some(ns) => for n in ns {
-                    // TODO error
-                    write!(&mut self.w, "${} ", n)?;
-                },
-                _ => ()
+            if illegal && self.config.normalize == Some(IllegalMoves::Flag) {
+                tokens.push(ILLEGAL_MOVE_COMMENT.to_string());
+                show_number = true;
             }
 
-            match game.get_comment(m) {
-                // TODO error
-                Some(s) => write!(&mut self.w, "{{{}}} ", s)?,
+            if let Some(comment) = self.comment_text(game.get_comment(m), game.get_commands(m), keep_comments) {
+                push_words(&mut tokens, &format!("{{{}}}", comment));
+                show_number = true;
+            }
+
+            match game.get_variations(m) {
+                Some(variations) if self.config.variation_depth != Some(0) =>
+                    for (v, variation) in variations.iter().enumerate() {
+                        let variation = if self.strips_variations(keep_comments) {
+                            self.rewrite_variation(variation, keep_comments, before_move)
+                        } else {
+                            variation.clone()
+                        };
+                        push_words(&mut tokens, &format!("({})", variation));
+
+                        let comment = game.get_after_variation_comment(m, v as i16);
+                        if let Some(comment) = self.comment_text(comment, None, keep_comments) {
+                            push_words(&mut tokens, &format!("{{{}}}", comment));
+                        }
+                        show_number = true;
+                    },
                 _ => ()
-            };
+            }
 
-            /*
-            List<MoveAnnotation> annotations = game.getAnnotations(m);
-            
-            if (annotations != null) {
-                for (MoveAnnotation annotation : annotations) {
-                    writer.write(annotation.getValue() + " ");
-                }
+            if active_color == ChessColor::Black {
+                full_move_number += 1;
             }
-                        
-            if (game.getVariations(m) != null) {
-                int i = 0;
-                for (String variation : game.getVariations(m)) {
-                    writer.write("(" + variation + ") ");
-                    String variationComment = game.getAfterVariationComment(m, i);
-                    if (variationComment != null) {
-                        writer.write("{" + variationComment + "} ");
+            active_color = other_color(active_color);
+        }
+
+        Ok((tokens, None))
+    }
+
+    /**
+     * The text of a comment and its commands, without what's excluded by the configuration.
+     */
+    fn comment_text(&self, comment: Option<&String>, commands: Option<&MoveCommands>, keep_comments: bool)
+            -> Option<String> {
+        let comment = comment.filter(|_| keep_comments)
+            .map(|it| if self.config.nocommands { split_commands(it).1 } else { it.clone() })
+            .filter(|it| !it.is_empty());
+        let commands = commands.filter(|_| !self.config.nocommands);
+
+        comment_with_commands(comment.as_ref(), commands)
+    }
+
+    /**
+     * Like `comment_text`, for a comment of a variation, whose commands are in the text.
+     */
+    fn variation_comment(&self, comment: &str, keep_comments: bool) -> Option<String> {
+        if keep_comments && !self.config.nocommands {
+            return Some(comment.to_string());
+        }
+
+        let (commands, text) = split_commands(comment);
+        let mut parts = if self.config.nocommands { Vec::new() } else { commands };
+        if keep_comments && !text.is_empty() {
+            parts.push(&text);
+        }
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(" "))
+        }
+    }
+
+    /**
+     * Writes a move in the configured notation, applying it to the position. If it cannot be applied the move is
+     * written as read and the position is removed.
+     */
+    fn move_text(&self, san: &str, position: &mut Option<ChessPosition>) -> String {
+        let result = match *position {
+            Some(ref mut p) => {
+                let before = *p;
+                p.apply_san_move(san).map(|it| match self.config.notation {
+                    Notation::Descriptive(language) => descriptive_move(&before, &it, language),
+                    notation => it.to_notation(notation)
+                })
+            },
+            None => return san.to_string()
+        };
+
+        result.unwrap_or_else(|_| {
+            *position = None;
+            san.to_string()
+        })
+    }
+
+    fn strips_variations(&self, keep_comments: bool) -> bool {
+        self.config.nonags || self.config.nocommands || !keep_comments || self.config.variation_depth.is_some() ||
+            self.config.notation != Notation::San || self.config.normalize.is_some()
+    }
+
+    /**
+     * Removes from the text of a variation of the main line the NAGs, comments and nested variations excluded by
+     * the configuration, and writes its moves in the configured notation starting from `position`.
+     */
+    fn rewrite_variation(&self, text: &str, keep_comments: bool, position: Option<ChessPosition>) -> String {
+        let mut tokens: Vec<String> = Vec::new();
+        // the depth of the current variation, 1 for the variation of the main line
+        let mut depth = 1;
+        // the depth of the nested variation being removed
+        let mut removed: Option<usize> = None;
+        let mut chars = text.chars().peekable();
+        // the positions before and after the last move, for every variation opened
+        let mut before_move: Option<ChessPosition> = None;
+        let mut after_move = position;
+        let mut opened: Vec<(Option<ChessPosition>, Option<ChessPosition>)> = Vec::new();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => {
+                    let comment: String = chars.by_ref().take_while(|it| *it != '}').collect();
+                    if removed.is_none() {
+                        if let Some(comment) = self.variation_comment(&comment, keep_comments) {
+                            tokens.push(format!("{{{}}}", comment));
+                        }
                     }
-                    i++;
-                }
-            }*/
-
-            let mut i = 0;
-            match game.get_variations(m) {   
-                Some(vs) => 
-                    for v in vs {
-                        write!(&mut self.w, "({}) ", v)?;
-                        match game.get_after_variation_comment(m, i) {
-                            // TODO error
-                            Some(c) => write!(&mut self.w, "{{{}}}", c)?,
-                            _ => ()
-                        };
-                    i += 1;
                 },
-                _ => ()
+                '(' => {
+                    depth += 1;
+                    // a nested variation replaces the last move
+                    opened.push((before_move, after_move));
+                    after_move = before_move;
+                    before_move = None;
+                    if removed.is_none() {
+                        if self.config.variation_depth.is_some_and(|max| depth > max) {
+                            removed = Some(depth);
+                        } else {
+                            tokens.push("(".to_string());
+                        }
+                    }
+                },
+                ')' => {
+                    match removed {
+                        Some(d) if d == depth => removed = None,
+                        Some(_) => (),
+                        None => tokens.push(")".to_string())
+                    }
+                    depth -= 1;
+                    if let Some((before, after)) = opened.pop() {
+                        before_move = before;
+                        after_move = after;
+                    }
+                },
+                _ if c.is_whitespace() => (),
+                _ => {
+                    let mut word = c.to_string();
+                    while let Some(&next) = chars.peek() {
+                        if next.is_whitespace() || next == '{' || next == '(' || next == ')' || next == '$' {
+                            break;
+                        }
+                        word.push(next);
+                        chars.next();
+                    }
+
+                    if removed.is_some() || (self.config.nonags && word.starts_with('$')) {
+                        continue;
+                    }
+
+                    // a move number can be followed by the move, like 1.e4
+                    if let Some(dot) = word.rfind('.').filter(|_| word.starts_with(|it: char| it.is_ascii_digit())) {
+                        let mv = word.split_off(dot + 1);
+                        tokens.push(word);
+                        if mv.is_empty() {
+                            continue;
+                        }
+                        word = mv;
+                    }
+
+                    if word.starts_with('$') || ["1-0", "0-1", "1/2-1/2", "*"].contains(&word.as_ref()) {
+                        tokens.push(word);
+                        continue;
+                    }
+
+                    let suffix = word.split_off(word.trim_end_matches(['!', '?']).len());
+                    before_move = after_move;
+                    let mv = self.move_text(&word, &mut after_move);
+                    tokens.push(if self.config.nonags { mv } else { mv + &suffix });
+                }
             }
-            
-            if active_color == ChessColor::White {
-                active_color = ChessColor::Black;
+        }
+
+        let mut result = String::with_capacity(text.len());
+        for token in tokens {
+            if !result.is_empty() && !result.ends_with('(') && token != ")" {
+                result.push(' ');
+            }
+            result.push_str(&token);
+        }
+        result
+    }
+
+    /**
+     * Writes the tokens separated by a space, in lines of at most 80 chars. A longer token is written in its own line.
+     */
+    fn write_wrapped(&mut self, tokens: &[String]) -> Result<(), Error> {
+        let mut line_length = 0;
+
+        for token in tokens {
+            let length = token.chars().count();
+
+            if line_length == 0 {
+                write!(&mut self.w, "{}", token)?;
+                line_length = length;
+            } else if line_length + 1 + length > MAX_LINE_LENGTH {
+                write!(&mut self.w, "\n{}", token)?;
+                line_length = length;
             } else {
-                active_color = ChessColor::White;
+                write!(&mut self.w, " {}", token)?;
+                line_length += 1 + length;
             }
+        }
 
-            if active_color == ChessColor::Black {
-                full_move_number += 1;
+        if line_length > 0 {
+            writeln!(&mut self.w)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * Returns the suffix of the move and the other NAGs to write after it.
+     */
+    fn nag_tokens(&self, nags: Option<&Vec<String>>) -> (&'static str, Vec<String>) {
+        let mut suffix = "";
+        let mut tokens = Vec::new();
+
+        for n in nags.into_iter().flatten() {
+            match Nag::parse(n) {
+                Some(nag) if self.config.nag_symbols && nag.is_suffix() && suffix.is_empty() =>
+                    suffix = nag.symbol().unwrap(),
+                _ => tokens.push(format!("${}", n))
             }
+        }
+
+        (suffix, tokens)
+    }
+
+}
+
+impl <W: Write> Drop for ChessWriter<W> {
+
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+
+}
+
+/**
+ * Adds the words of a comment or a variation, a new line can be written between any two of them. The spaces are
+ * kept, so the text is the same when it's read again.
+ */
+fn push_words(tokens: &mut Vec<String>, text: &str) {
+    let mut words = text.split(' ');
+    let mut word = words.next().unwrap_or_default().to_string();
+
+    for next in words {
+        // more spaces in a row stay in the same token
+        if next.is_empty() || word.ends_with(' ') || word.is_empty() {
+            word.push(' ');
+            word.push_str(next);
+        } else {
+            tokens.push(word);
+            word = next.to_string();
+        }
+    }
+    tokens.push(word);
+}
+
+/**
+ * Splits the commands of a comment, also the unknown ones, from its text.
+ * # Returns
+ * the commands, as written, and the text without them.
+ */
+fn split_commands(comment: &str) -> (Vec<&str>, String) {
+    let mut commands = Vec::new();
+    let mut parts: Vec<&str> = Vec::new();
+    let mut rest = comment;
 
-            //position = position.positionAfterMove(move);
+    while let Some(start) = rest.find("[%") {
+        match rest[start..].find(']') {
+            Some(end) => {
+                parts.push(&rest[..start]);
+                commands.push(&rest[start..start + end + 1]);
+                rest = &rest[start + end + 1..];
+            },
+            None => break
         }
-        Result::Ok(())
     }
+    parts.push(rest);
+
+    let text: Vec<&str> = parts.iter().map(|it| it.trim()).filter(|it| !it.is_empty()).collect();
+    (commands, text.join(" "))
+}
+
+/**
+ * The commands, like `[%clk 0:05:00]`, followed by the text of the comment.
+ */
+pub(crate) fn comment_with_commands(comment: Option<&String>, commands: Option<&MoveCommands>) -> Option<String> {
+    match (comment, commands) {
+        (Some(c), Some(cs)) => Some(format!("{} {}", cs, c)),
+        (None, Some(cs)) => Some(cs.to_string()),
+        (Some(c), None) => Some(c.to_string()),
+        (None, None) => None
+    }
+}
 
+/**
+ * Escapes the quotes and the backslashes in a tag value.
+ */
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
 }
\ No newline at end of file
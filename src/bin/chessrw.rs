@@ -6,11 +6,11 @@ extern crate indexmap;
 use std::io::{Error, ErrorKind};
 use std::fs;
 use std::fs::File;
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread;
+use std::io;
+use std::io::{Read, Write};
 use std::time::Instant;
 use std::time::Duration;
+use std::sync::Arc;
 
 use clap::{Arg, App, ArgMatches, AppSettings};
 use separator::Separatable;
@@ -21,6 +21,30 @@ use chessrw::base::writer::*;
 use chessrw::base::position::*;
 use chessrw::base::fen::*;
 use chessrw::base::game::*;
+use chessrw::base::index::*;
+use chessrw::base::encoding::*;
+use chessrw::base::filter::*;
+use chessrw::base::json::{NdjsonIterator, read_json};
+use chessrw::base::compression::CompressedReader;
+use chessrw::base::notation::{notation_from_name, input_notation_from_name};
+
+/**
+ * The name of the input or the output to read from the standard input or write to the standard output.
+ */
+const STDIO: &str = "-";
+
+/**
+ * Prints a message to the standard output, or to the standard error when the games are written to the standard output.
+ */
+macro_rules! status {
+    ($to_stdout:expr, $($arg:tt)*) => {
+        if $to_stdout {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
 
 /**
  * ficsgamesdb_201801_standard_nomovetimes_14117.pgn
@@ -43,38 +67,105 @@ pub fn main() -> std::io::Result<()> {
             .setting(AppSettings::DeriveDisplayOrder)
             .version("0.1.0")
             .arg(Arg::with_name("INPUT")
-                .help("Sets the input file to use")
+                .help("Sets the input file to use, it can be compressed with gzip, bzip2, xz or zstd. \
+                    Files with extension .json or .ndjson are read as JSON games. Use - for the standard input")
                 .required(true)
                 .index(1))
             .arg(Arg::with_name("OUTPUT")
-                .help("Sets the output file to use")
+                .help("Sets the output file to use, - for the standard output")
                 .required(false)
                 .index(2))
             .arg(Arg::with_name("nocomments").long("nocomments"))
             .arg(Arg::with_name("novariations").long("novariations"))
             .arg(Arg::with_name("notags").long("notags"))
+            .arg(Arg::with_name("canonicaltags").long("canonicaltags").help("Write the seven tag roster first, \
+                filling the missing tags, then the other tags."))
+            .arg(Arg::with_name("sorttags").long("sorttags").help("Like --canonicaltags, with the other tags sorted."))
+            .arg(Arg::with_name("knowntags").long("knowntags").help("Like --canonicaltags, without unknown tags."))
+            .arg(Arg::with_name("nonags").long("nonags").help("Write no NAGs and move assessments, like !?."))
+            .arg(Arg::with_name("nocommands").long("nocommands").help("Remove the commands, like [%clk 0:05:00], \
+                from the comments."))
+            .arg(Arg::with_name("nagsymbols").long("nagsymbols").help("Write the move assessments $1 to $6 as suffixes, like ! or ?!, instead of $n."))
             .arg(Arg::with_name("onlymoves").long("onlymoves").help("Write only moves (alias for --nocomments --novariations --notags)."))
+            .arg(Arg::with_name("enrich").long("enrich").help("Write the PlyCount tag counted from the moves and the \
+                same result in the Result tag and after the moves, a final checkmate or stalemate decides it, a different \
+                Result tag is kept in OriginalResult."))
+            .arg(Arg::with_name("finalfen").long("finalfen").help("Write a FinalFEN tag with the position at the end \
+                of the moves."))
+            .arg(Arg::with_name("termination").long("termination").help("Write the Termination tag: normal if the \
+                game ends with a checkmate or a stalemate, unterminated if its result is *."))
             .arg(Arg::with_name("players").long("players").takes_value(true).help("A comma separated list of players. \
                 Put an * as first character to get only games between players. \
                 Put a +, - or = as first character of a player to get only wins, loses or draws for that player."))
             .arg(Arg::with_name("whitewins").long("whitewins"))
             .arg(Arg::with_name("blackwins").long("blackwins"))
             .arg(Arg::with_name("draw").long("draw"))
-            .arg(Arg::with_name("minplycount").long("minplycount").takes_value(true))
+            .arg(Arg::with_name("minplycount").long("minplycount").takes_value(true).help("Only the games with at least \
+                the given number of plies, counted from the moves instead of the PlyCount tag."))
             .arg(Arg::with_name("fen").long("fen").takes_value(true))
-            .arg(Arg::with_name("threads").long("threads").takes_value(true).help("Number of threads to use, it's used only when fen is used."))
+            .arg(Arg::with_name("threads").long("threads").takes_value(true).help("Number of threads to use to parse the file and, when fen is used, to search the position."))
             .arg(Arg::with_name("noprogress").long("noprogress").help("No progress bar is showed (faster)."))
+            .arg(Arg::with_name("buildindex").long("buildindex").help("Builds the index of the games of the input file \
+                in <INPUT>.idx, then exits."))
+            .arg(Arg::with_name("game").long("game").takes_value(true).help("Starts reading from the game with the given \
+                number (one based), using the index built with --buildindex."))
+            .arg(Arg::with_name("limit").long("limit").takes_value(true).help("The maximum number of games to read."))
+            .arg(Arg::with_name("writeindex").long("writeindex").takes_value(true).help("Writes the index of the games \
+                read (after filtering) to the given file."))
+            .arg(Arg::with_name("readindex").long("readindex").takes_value(true).help("Reads only the games of the given \
+                index file, written with --writeindex."))
+            .arg(Arg::with_name("encoding").long("encoding").takes_value(true).help("The encoding of the input file: \
+                utf-8, latin1 or windows-1252. By default every line is read as UTF-8 if it's valid, otherwise as windows-1252."))
+            .arg(Arg::with_name("inputnotation").long("inputnotation").takes_value(true).help("The notation of the \
+                moves of the input file: san (default), figurine, coordinate (like e2e4 or e2-e4), iccf (like 5254), \
+                descriptive (like P-K4), descriptive:es (like P4R), a language code for localized piece letters (de, es, fr, it, nl, pl, \
+                pt or sv) or auto to detect them."))
+            .arg(Arg::with_name("outputencoding").long("outputencoding").takes_value(true).help("The encoding of the \
+                output file: utf-8 (default), latin1 or windows-1252."))
+            .arg(Arg::with_name("outputformat").long("outputformat").takes_value(true).help("The format of the \
+                output file: pgn (default), json, ndjson (a JSON game for every line), csv or tsv."))
+            .arg(Arg::with_name("variationdepth").long("variationdepth").takes_value(true).help("Write only the \
+                variations up to the given depth, 1 for the variations of the main line without the nested ones."))
+            .arg(Arg::with_name("annotator").long("annotator").takes_value(true).help("Write the comments only for \
+                the games whose Annotator tag contains the given name."))
+            .arg(Arg::with_name("notation").long("notation").takes_value(true).help("The notation of the moves: \
+                san (default), lan (long algebraic), uci, iccf (numeric, like 5254), figurine, descriptive (like P-K4), descriptive:es (like P4R) \
+                or a language code for localized piece letters: de, es, fr, it, nl, pl, pt or sv."))
+            .arg(Arg::with_name("normalize").long("normalize").takes_value(true).help("Replays the moves to write \
+                them normalized (O-O, minimal disambiguation, e8=Q, right check and mate signs). An illegal move is \
+                followed by an \"Illegal move\" comment with flag, or ends the game with result * with truncate."))
+            .arg(Arg::with_name("columns").long("columns").takes_value(true).help("A comma separated list of the \
+                columns of the csv and tsv output: tag names or @plycount, @finalfen, @opening (the first 10 plies) and \
+                @opening:N. By default the seven tag roster."))
             .get_matches();
 
     let input = matches.value_of("INPUT").unwrap();
+    let from_stdin = input == STDIO;
+    let to_stdout = matches.value_of("OUTPUT") == Some(STDIO);
 
-    println!("Reading file {} ...", &input);
+    status!(to_stdout, "Reading file {} ...", &input);
 
     let tags_filter = TagsFilter::new(&matches);
 
-    let fun = |tags: &IndexMap<String,String>| tags_filter.filter(tags);
+    let min_ply_count = match matches.value_of("minplycount") {
+        Some(count) => Some(count.parse::<usize>()
+            .map_err(|e| Error::new(ErrorKind::Other, format!("Error parsing minplycount option: {}", e)))?),
+        None => None
+    };
+
+    let input_encoding = parse_encoding(&matches, "encoding")?;
+    let output_encoding = parse_encoding(&matches, "outputencoding")?;
 
     let mut builder = ChessParserBuilder::new();
+
+    if let Some(encoding) = input_encoding {
+        builder.input_encoding(encoding);
+    }
+
+    if let Some(notation) = matches.value_of("inputnotation") {
+        builder.input_notation(input_notation_from_name(notation)
+            .map_err(|e| Error::new(ErrorKind::Other, format!("Error parsing inputnotation option: {}", e)))?);
+    }
     
     let only_moves = matches.is_present("onlymoves");
 
@@ -86,29 +177,13 @@ pub fn main() -> std::io::Result<()> {
         builder.ignore_variations();
     }
 
-    if tags_filter.apply() {
-        builder.tag_filter(&fun);
-    }
-
-    let metadata = fs::metadata(&input)?;
-    let file = File::open(&input);
-    println!("File size: {} bytes.", metadata.len().separated_string());
-
-    builder.file_size(metadata.len());
-
-    builder.progress(!matches.is_present("noprogress") && metadata.len() >= 1_000_000);
-
-    let p = builder.build();
-
-    let start = Instant::now();
-
-    let position = if matches.is_present("fen") {
+    let position_filter = if matches.is_present("fen") {
         let fen = matches.value_of("fen");
         if fen.is_some() {
             let result = FEN_PARSER.parse(fen.unwrap());
             
             match result {
-                Ok(p) => Some(p),
+                Ok(p) => Some(PositionFilter::new(p)),
                 Err(e) => return Err(Error::new(ErrorKind::Other, format!("Error parsing fen option: {}", e)))
             }
         } else {
@@ -118,175 +193,392 @@ pub fn main() -> std::io::Result<()> {
         None
     };
 
-    let threads = if matches.is_present("threads") && matches.is_present("fen") {
-        matches.value_of("threads").unwrap().parse::<u8>().unwrap()
-    } else {
-        0
-    };
+    let mut game_filters: Vec<Box<dyn GameFilter>> = Vec::new();
+
+    if tags_filter.apply() {
+        game_filters.push(Box::new(TagFilter::new(move |tags: &IndexMap<String,String>| tags_filter.filter(tags))));
+    }
+
+    if let Some(filter) = position_filter {
+        game_filters.push(Box::new(filter));
+    }
+
+    // the PlyCount tag is often missing or wrong, the moves are counted
+    if let Some(min_ply_count) = min_ply_count {
+        game_filters.push(Box::new(MovesFilter::new(move |game: &dyn ChessGame| game.get_moves().len() >= min_ply_count)));
+    }
 
-    let mut parsed = p.parse(file.unwrap());
+    // the filters are shared with the games read from JSON, that are not parsed
+    let game_filter = if game_filters.is_empty() { None } else { Some(Arc::new(And::new(game_filters))) };
 
-    if matches.is_present("OUTPUT") {
-        let file_to_write = File::create(matches.value_of("OUTPUT").unwrap());
+    if let Some(ref filter) = game_filter {
+        builder.game_filter(filter.clone());
+    }
+
+    // the size of the standard input is unknown
+    let file_size = if from_stdin { 0 } else { fs::metadata(&input)?.len() };
+    if !from_stdin {
+        status!(to_stdout, "File size: {} bytes.", file_size.separated_string());
+    }
+
+    builder.file_size(file_size);
+
+    builder.progress(!matches.is_present("noprogress") && file_size >= 1_000_000);
+
+    let p = builder.build();
+
+    let start = Instant::now();
+
+    if matches.is_present("buildindex") {
+        if from_stdin {
+            return Err(Error::new(ErrorKind::Other, "Option buildindex cannot be used with the standard input."));
+        }
+
+        let mut index_builder = ChessParserBuilder::new();
+        index_builder.ignore_comments();
+        index_builder.ignore_variations();
+        if let Some(encoding) = input_encoding {
+            index_builder.input_encoding(encoding);
+        }
+        index_builder.file_size(file_size);
+        index_builder.progress(!matches.is_present("noprogress") && file_size >= 1_000_000);
+
+        // the offsets of a compressed file cannot be sought
+        let mut iterator = index_builder.build().parse_seekable(File::open(input)?)
+            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+        let index = GameIndex::build(&mut iterator);
+        let index_path = GameIndex::sidecar_path(input);
+        index.save(&index_path)?;
+
+        status!(to_stdout, "{} games indexed in {} in {}.", index.len().separated_string(), index_path,
+            format_duration(start.elapsed()));
+        return Result::Ok(());
+    }
+
+    let threads = match matches.value_of("threads") {
+        Some(t) => match t.parse::<usize>() {
+            Ok(value) => value,
+            Err(e) => return Err(Error::new(ErrorKind::Other, format!("Error parsing threads option: {}", e)))
+        },
+        None => 1
+    };
+
+    let writer = if let Some(output) = matches.value_of("OUTPUT") {
+        let file_to_write: Box<dyn Write> = if output == STDIO {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(output)?)
+        };
         let mut chess_writer_builder = ChessWriterBuilder::new();
 
         if matches.is_present("notags") || only_moves {
             chess_writer_builder.notags();
         }
 
-        let count = if threads > 1 {
-            iterate_with_threads(&mut parsed, position, Some(chess_writer_builder.build(file_to_write.unwrap())), threads)
-        } else {
-            let chess_writer = ::std::cell::RefCell::new(chess_writer_builder.build(file_to_write.unwrap()));
+        if matches.is_present("canonicaltags") {
+            chess_writer_builder.canonical_tags();
+        }
 
-            iterate(&mut parsed, position, |it| chess_writer.borrow_mut().write(it).unwrap())
-        };
+        if matches.is_present("sorttags") {
+            chess_writer_builder.sort_tags();
+        }
+
+        if matches.is_present("enrich") {
+            chess_writer_builder.enrich();
+        }
+
+        if matches.is_present("finalfen") {
+            chess_writer_builder.final_fen();
+        }
+
+        if matches.is_present("termination") {
+            chess_writer_builder.termination();
+        }
+
+        if matches.is_present("knowntags") {
+            chess_writer_builder.known_tags_only();
+        }
+
+        if matches.is_present("nagsymbols") {
+            chess_writer_builder.nag_symbols();
+        }
+
+        if matches.is_present("nonags") {
+            chess_writer_builder.nonags();
+        }
+
+        if matches.is_present("nocommands") {
+            chess_writer_builder.nocommands();
+        }
+
+        if let Some(depth) = matches.value_of("variationdepth") {
+            chess_writer_builder.variation_depth(depth.parse::<usize>()
+                .map_err(|e| Error::new(ErrorKind::Other, format!("Error parsing variationdepth option: {}", e)))?);
+        }
+
+        if let Some(notation) = matches.value_of("notation") {
+            chess_writer_builder.notation(notation_from_name(notation)
+                .map_err(|e| Error::new(ErrorKind::Other, format!("Error parsing notation option: {}", e)))?);
+        }
+
+        if let Some(illegal_moves) = matches.value_of("normalize") {
+            chess_writer_builder.normalize(illegal_moves_from_name(illegal_moves)
+                .map_err(|e| Error::new(ErrorKind::Other, format!("Error parsing normalize option: {}", e)))?);
+        }
+
+        if let Some(annotator) = matches.value_of("annotator") {
+            chess_writer_builder.annotator(annotator);
+        }
+
+        if let Some(encoding) = output_encoding {
+            chess_writer_builder.encoding(encoding);
+        }
+
+        if let Some(format) = matches.value_of("outputformat") {
+            chess_writer_builder.format(output_format_from_name(format)
+                .map_err(|e| Error::new(ErrorKind::Other, format!("Error parsing outputformat option: {}", e)))?);
+        }
+
+        if let Some(columns) = matches.value_of("columns") {
+            chess_writer_builder.columns(columns.split(',').map(CsvColumn::from_name).collect::<Result<Vec<_>, _>>()
+                .map_err(|e| Error::new(ErrorKind::Other, format!("Error parsing columns option: {}", e)))?);
+        }
+
+        Some(chess_writer_builder.build(file_to_write))
+    } else {
+        None
+    };
+
+    let write = writer.is_some();
 
-        println!("{} games written in {}.", count.separated_string(), format_duration(start.elapsed()));
+    let limit = match matches.value_of("limit") {
+        Some(l) => match l.parse::<i64>() {
+            Ok(value) => Some(value),
+            Err(e) => return Err(Error::new(ErrorKind::Other, format!("Error parsing limit option: {}", e)))
+        },
+        None => None
+    };
+
+    let indexed = matches.is_present("game") || matches.is_present("readindex") || matches.is_present("writeindex");
+
+    if threads > 1 && (indexed || limit.is_some()) {
+        return Err(Error::new(ErrorKind::Other, "Options game, limit, readindex and writeindex cannot be used with threads."));
+    }
+
+    if from_stdin && (indexed || threads > 1) {
+        return Err(Error::new(ErrorKind::Other, "Options game, readindex, writeindex and threads cannot be used with the \
+            standard input."));
+    }
+
+    let (count, errors) = if input.ends_with(".json") || input.ends_with(".ndjson") {
+        let (games, errors) = read_json_games(input)?;
+        let mut games = games.into_iter()
+            .filter(|game| game_filter.as_ref().is_none_or(|filter| accept_game(filter.as_ref(), game)))
+            .take(limit.map_or(usize::MAX, |l| l as usize));
+        let count = write_games(&mut games, writer);
+
+        (count, errors)
+    } else if threads > 1 {
+        let mut parsed = p.parse_parallel(File::open(input)?, threads);
+
+        let count = write_games(&mut parsed, writer);
+
+        (count, parsed.get_errors().clone())
     } else {
-        let count = if threads > 1 {
-            iterate_with_threads(&mut parsed, position, None, threads)
+        let chess_writer = ::std::cell::RefCell::new(writer);
+        let index_to_write = ::std::cell::RefCell::new(GameIndex::new());
+
+        let consumer = |it: &ChessGame, entry: GameIndexEntry| {
+            match *chess_writer.borrow_mut() {
+                Some(ref mut x) => write_game(x, it),
+                None => (),
+            };
+            index_to_write.borrow_mut().push(entry);
+        };
+
+        let result = if matches.is_present("readindex") {
+            let index = GameIndex::load(matches.value_of("readindex").unwrap())?;
+            let mut parsed = p.parse_indexed(File::open(input)?, &index).map_err(|e| Error::new(ErrorKind::Other, e))?;
+
+            let count = iterate(&mut parsed, limit, &consumer);
+
+            (count, parsed.get_errors().clone())
+        } else if matches.is_present("game") {
+            let game = match matches.value_of("game").unwrap().parse::<usize>() {
+                Ok(value) if value > 0 => value,
+                _ => return Err(Error::new(ErrorKind::Other, "Error parsing game option: it must be a positive number."))
+            };
+
+            let index_path = GameIndex::sidecar_path(input);
+            let index = GameIndex::load(&index_path).map_err(|e| Error::new(ErrorKind::Other,
+                format!("Cannot read index {}, build it with --buildindex: {}", index_path, e)))?;
+
+            let entry = match index.get(game - 1) {
+                Some(entry) => entry,
+                None => return Err(Error::new(ErrorKind::Other, format!("There are only {} games.", index.len())))
+            };
+
+            let mut parsed = p.parse_at(File::open(input)?, entry).map_err(|e| Error::new(ErrorKind::Other, e))?;
+
+            let count = iterate(&mut parsed, limit, &consumer);
+
+            (count, parsed.get_errors().clone())
+        } else if from_stdin {
+            let mut parsed = p.parse_reader(CompressedReader::new(io::stdin()));
+
+            let count = iterate(&mut parsed, limit, &consumer);
+
+            (count, parsed.get_errors().clone())
         } else {
-            iterate(&mut parsed, position, |_| ())
+            let mut parsed = p.parse(File::open(input)?);
+
+            let count = iterate(&mut parsed, limit, &consumer);
+
+            (count, parsed.get_errors().clone())
         };
 
-        println!("{} games red in {}.", count.separated_string(), format_duration(start.elapsed()));
+        if let Some(ref mut chess_writer) = *chess_writer.borrow_mut() {
+            chess_writer.finish()?;
+        }
+
+        if matches.is_present("writeindex") {
+            index_to_write.borrow().save(matches.value_of("writeindex").unwrap())?;
+        }
+
+        result
+    };
+
+    if write {
+        status!(to_stdout, "{} games written in {}.", count.separated_string(), format_duration(start.elapsed()));
+    } else {
+        status!(to_stdout, "{} games red in {}.", count.separated_string(), format_duration(start.elapsed()));
     }
 
-    if !&parsed.get_errors().is_empty() {
-        println!("{} errors found:", &parsed.get_errors().len());
-        &parsed.get_errors().iter().for_each(|s| println!("{}", s));
+    if !errors.is_empty() {
+        status!(to_stdout, "{} errors found:", errors.len());
+        errors.iter().for_each(|s| status!(to_stdout, "{}", s));
     }
 
     Result::Ok(())
 }
 
-fn iterate<F>(iterator: &mut ChessParserIterator<File>, position: Option<ChessPosition>, f: F) -> i64 where F: Fn(&ChessGame) -> () {
-    let mut count = 0;
-    let mut tot_count = 1;
-
-    while iterator.next_temp() {
-        if position.is_some() {
-            if match contains(iterator, &position.unwrap()) {
-                Ok(value) => value,
-                Err(e) => {
-                    println!("Error in game {}, {}.", tot_count, e);
-                    false
-                }
-            } {
-                f(iterator);
-                count += 1;
-            }
-        } else {
-            f(iterator);
-            count += 1;
-        }
-        tot_count += 1;
-    }
-    count
+/**
+ * A source of games, to iterate in the same way over all the games of a file or only over some of them.
+ */
+trait GameSource {
+
+    fn next_game(&mut self) -> bool;
+
+    fn game(&self) -> &ChessGame;
+
+    fn index_entry(&self) -> GameIndexEntry;
+
 }
 
-fn iterate_with_threads(iterator: &mut ChessParserIterator<File>, position: Option<ChessPosition>, writer: Option<ChessWriter>, 
-        threads_count: u8) -> i64 {
-    let count_arc = Arc::new(Mutex::new(0));
-    let ended_arc = Arc::new(AtomicBool::new(false));
-    let games_to_check_arc : Arc<Mutex<Vec<ChessGameImpl>>> = Arc::new(Mutex::new(Vec::new()));
-    let writer_arc = Arc::new(Mutex::new(writer));
+impl <R: Read> GameSource for ChessParserIterator<R> {
 
-    let mut tot_count = 1;
+    fn next_game(&mut self) -> bool {
+        self.next_temp()
+    }
 
-    let mut threads = Vec::new();
+    fn game(&self) -> &ChessGame {
+        self
+    }
 
-    for _ in 0..threads_count {
-        let games_to_check_for_thread = games_to_check_arc.clone();
-        let count_for_thread = count_arc.clone();
-        let ended_for_thread = ended_arc.clone();
-        let writer_for_thread = writer_arc.clone();
+    fn index_entry(&self) -> GameIndexEntry {
+        self.get_game_index_entry()
+    }
 
-        threads.push(thread::spawn(move || {
-            loop {
+}
 
-                let mut game = None;
+impl GameSource for IndexedParserIterator {
 
-                {
-                    let mut locked_gtc = games_to_check_for_thread.lock().unwrap();
-                    game = locked_gtc.pop();
-                }
+    fn next_game(&mut self) -> bool {
+        self.next_temp()
+    }
 
-                if game.is_some() {
-                    let g = game.unwrap();
-
-                    if match contains(&g, &position.unwrap()) {
-                            Ok(value) => value,
-                            Err(e) => {
-                                println!("Error in game {}, {}.", tot_count, e);
-                                false
-                            }
-                        } {
-                            {
-                                let mut locked_ct = count_for_thread.lock().unwrap();
-                                *locked_ct += 1;
-                            }
-
-                            let mut locked_writer = writer_for_thread.lock().unwrap();
-
-                            match *locked_writer {
-                                    Some(ref mut x) => x.write(&g).unwrap(),
-                                    None => (),
-                                };                            
-                        }
-                } else {
-                    if ended_for_thread.load(Ordering::Relaxed) {
-                        break;
-                    }
-                }
-                
-                // if !&game.is_some() {
-                    // thread::sleep(Duration::from_millis(5));
-                // }
-            }
-        }));
+    fn game(&self) -> &ChessGame {
+        IndexedParserIterator::game(self)
     }
 
-    let games_to_check_for_main = games_to_check_arc.clone();
+    fn index_entry(&self) -> GameIndexEntry {
+        IndexedParserIterator::game(self).get_game_index_entry()
+    }
+
+}
 
-    while iterator.next_temp() {
-        let mut locked_gtc = games_to_check_for_main.lock().unwrap();
-        locked_gtc.push(iterator.to_game());
-        tot_count += 1;
+fn iterate<S,F>(iterator: &mut S, limit: Option<i64>, f: F) -> i64
+        where S: GameSource, F: Fn(&ChessGame, GameIndexEntry) -> () {
+    let mut count = 0;
+
+    while limit.map_or(true, |l| count < l) && iterator.next_game() {
+        f(iterator.game(), iterator.index_entry());
+        count += 1;
     }
+    count
+}
+
+fn write_games<I, W>(iterator: &mut I, writer: Option<ChessWriter<W>>) -> i64
+        where I: Iterator<Item=ChessGameImpl>, W: Write {
+    let mut writer = writer;
+    let mut count = 0;
 
-    {
-        let ended_for_main = ended_arc.clone();
-        ended_for_main.store(true, Ordering::Relaxed);
+    for game in iterator {
+        match writer {
+            Some(ref mut x) => write_game(x, &game),
+            None => (),
+        };
+        count += 1;
     }
 
-    // println!("Waiting for threads.", );
+    if let Some(ref mut x) = writer {
+        x.finish().unwrap();
+    }
+    count
+}
 
-    for thread in threads {
-        thread.join().unwrap();
+/**
+ * Writes a game, when the output is a pipe closed by the reader (for example `head`) the program ends.
+ */
+fn write_game<W: Write>(writer: &mut ChessWriter<W>, game: &dyn ChessGame) {
+    match writer.write(game) {
+        Ok(()) => (),
+        Err(ref e) if e.kind() == ErrorKind::BrokenPipe => ::std::process::exit(0),
+        Err(e) => panic!("Error writing game: {}", e)
     }
+}
+
+/**
+ * Reads all the games of a JSON or NDJSON file.
+ */
+fn read_json_games(input: &str) -> Result<(Vec<ChessGameImpl>, Vec<String>), Error> {
+    let file = File::open(input)?;
 
-    let count_for_thread = count_arc.clone();
-    let count = count_for_thread.lock().unwrap();
-    *count
+    if input.ends_with(".ndjson") {
+        let mut iterator = NdjsonIterator::new(file);
+        let games = iterator.by_ref().collect();
+        Ok((games, iterator.get_errors().clone()))
+    } else {
+        let games = read_json(file).map_err(|e| Error::new(ErrorKind::Other, e))?;
+        Ok((games, Vec::new()))
+    }
 }
 
-fn contains(game: &ChessGame, position: &ChessPosition) -> Result<bool,String> {
-    let mut p = game.initial_position().unwrap().clone();
-    let mut count = 1;
-    for mv in game.get_moves().iter() {
-        // println!("Move {} (n. {})", mv, count);
-        let applied = p.apply_move(mv);
-        if applied.is_some() {
-            return Err(format!("move n. {}: {}", count, applied.unwrap()));
-        }
-        if p.board == position.board {
-            return Ok(true);
-        }
-        count += 1;
+/**
+ * Applies the filters to a game read from JSON, like the parser does for the PGN games.
+ */
+fn accept_game(filter: &dyn GameFilter, game: &ChessGame) -> bool {
+    filter.accept_tags(game.get_tags()) && (!filter.needs_game() || filter.accept_game(game).unwrap_or(false))
+}
+
+fn parse_encoding(matches: &ArgMatches, option: &str) -> Result<Option<Encoding>,Error> {
+    match matches.value_of(option) {
+        Some(name) => match encoding_from_name(name) {
+            Ok(encoding) => Ok(Some(encoding)),
+            Err(e) => Err(Error::new(ErrorKind::Other, format!("Error parsing {} option: {}", option, e)))
+        },
+        None => Ok(None)
     }
-    Ok(false)
 }
 
 fn format_duration(duration: Duration) -> String {
@@ -300,25 +592,23 @@ fn format_duration(duration: Duration) -> String {
     }
 }
 
-struct TagsFilter<'a> {
+struct TagsFilter {
     white_wins: bool,
     black_wins: bool,
     draw: bool,
-    min_ply_count: Option<&'a str>,
-    players: Option<&'a str>,
+    players: Option<String>,
 }
 
-impl <'a> TagsFilter<'a> {
+impl TagsFilter {
 
-    fn new(matches: &'a ArgMatches<'a>) -> TagsFilter {
+    fn new(matches: &ArgMatches) -> TagsFilter {
         TagsFilter{ white_wins: matches.is_present("whitewins"), black_wins: matches.is_present("blackwins"), 
-            draw: matches.is_present("draw"), min_ply_count: matches.value_of("minplycount"),
-            players : matches.value_of("players")}
+            draw: matches.is_present("draw"),
+            players : matches.value_of("players").map(String::from)}
     }
 
     fn filter(&self, tags: &IndexMap<String,String>) -> bool {
         (!self.apply_result() || self.filter_result(tags)) &&
-        (!self.apply_ply_count() || self.filter_ply_count(tags)) &&
         (!self.apply_players() || self.filter_players(tags))
     }
 
@@ -330,22 +620,11 @@ impl <'a> TagsFilter<'a> {
         )
     }
 
-    fn filter_ply_count(&self, tags: &IndexMap<String,String>) -> bool {
-        // TODO parse error
-        let min_ply_count = self.min_ply_count.unwrap().parse::<i32>().unwrap();
-        tags.get("PlyCount").map_or_else(|| false, |r| 
-            match r.parse::<i32>() {
-                Ok(ply_count) => ply_count >= min_ply_count,
-                _ => false
-            }
-        )
-    }
-
     fn filter_players(&self, tags: &IndexMap<String,String>) -> bool {
         tags.get("White").map_or_else(|| false, |wp| 
             tags.get("Black").map_or_else(|| false, |bp| {
                 let mut result = false;
-                let mut players = self.players.unwrap().to_lowercase();
+                let mut players = self.players.as_ref().unwrap().to_lowercase();
                 let between = players.chars().next().unwrap() == '*';
 
                 if between {
@@ -398,17 +677,13 @@ impl <'a> TagsFilter<'a> {
     }
 
     fn apply(&self) -> bool {
-        self.apply_result() || self.apply_ply_count() || self.apply_players()
+        self.apply_result() || self.apply_players()
     }
 
     fn apply_result(&self) -> bool {
         self.white_wins || self.black_wins || self.draw
     }
 
-    fn apply_ply_count(&self) -> bool {
-        self.min_ply_count.is_some()
-    }
-
     fn apply_players(&self) -> bool {
         self.players.is_some()
     }
@@ -421,8 +696,7 @@ fn tags_filter_players(players: &str) -> TagsFilter {
         white_wins: false,
         black_wins: false,
         draw: false,
-        min_ply_count: None,
-        players: Some(players),
+        players: Some(players.to_string()),
     }
 }
 
@@ -510,4 +784,19 @@ fn filter_players_between_wins_impossible_fail() {
     let tags = white_vs_black("Capablanca, Jose Raul", "Corzo y Prinzipe, Juan", Some("1-0"));
 
     assert_eq!(tags_filter.filter_players(&tags), false);
-}
\ No newline at end of file
+}
+#[test]
+fn filter_json_games() {
+    let p = ChessParserBuilder::new().build();
+    let games: Vec<ChessGameImpl> = p.parse_string("[White \"Capablanca\"]\n[Black \"Lasker\"]\n\n1. e4 e5 *\n\n\
+        [White \"Capablanca\"]\n[Black \"Lasker\"]\n\n1. e4 e5 2. Nf3 *\n\n\
+        [White \"Lasker\"]\n[Black \"Tarrasch\"]\n\n1. d4 d5 2. c4 e6 *").collect();
+
+    let players = tags_filter_players("capablanca");
+    let filter = And::new(vec![Box::new(TagFilter::new(move |tags: &IndexMap<String,String>| players.filter(tags))),
+        Box::new(MovesFilter::new(|game: &dyn ChessGame| game.get_moves().len() >= 3))]);
+
+    assert!(!accept_game(&filter, &games[0]));
+    assert!(accept_game(&filter, &games[1]));
+    assert!(!accept_game(&filter, &games[2]));
+}
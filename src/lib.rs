@@ -3,8 +3,23 @@ extern crate strum;
 extern crate strum_macros;
 #[macro_use]
 extern crate lazy_static;
+#[cfg(feature = "progressbar")]
 extern crate indicatif;
 extern crate indexmap;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "bzip2")]
+extern crate bzip2;
+#[cfg(feature = "xz")]
+extern crate xz2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+extern crate memmap2;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
 
 pub mod base;
 mod tests;
\ No newline at end of file
@@ -8,6 +8,8 @@ use base::game::*;
 use base::parser::*;
 use base::writer::ChessWriterBuilder;
 use base::position::*;
+#[cfg(test)]
+use base::index::*;
 
 #[cfg(test)]
 
@@ -35,6 +37,639 @@ fn parse_kramnik() {
     assert_eq!(p.parse(file.unwrap()).count(), 40);
 }
 
+#[test]
+#[cfg(feature = "compression")]
+fn parse_compressed_kramnik() {
+    let mut builder = ChessParserBuilder::new();
+    builder.ignore_comments();
+    builder.ignore_variations();
+    let p = builder.build();
+
+    for ext in ["gz", "bz2", "xz", "zst"].iter() {
+        let file = File::open(format!("testresources/kramnik.pgn.{}", ext));
+
+        assert_eq!(p.parse(file.unwrap()).count(), 40, "{}", ext);
+    }
+}
+
+#[test]
+#[cfg(not(feature = "gzip"))]
+fn compressed_without_feature() {
+    let mut reader = ::base::compression::CompressedReader::new(File::open("testresources/kramnik.pgn.gz").unwrap());
+
+    let error = reader.read_to_string(&mut String::new()).unwrap_err();
+    assert!(error.to_string().contains("gzip feature"));
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn compressed_reader_counts_compressed_bytes() {
+    let file = File::open("testresources/kramnik.pgn.gz").unwrap();
+    let mut reader = ::base::compression::CompressedReader::new(file);
+
+    let mut content = String::new();
+    reader.read_to_string(&mut content).unwrap();
+
+    assert_eq!(Some(::base::compression::Compression::Gzip), reader.compression());
+    assert_eq!(67365, content.len());
+    assert_eq!(25034, reader.read_bytes().load(::std::sync::atomic::Ordering::Relaxed));
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn parse_truncated_compressed() {
+    let mut bytes = Vec::new();
+    File::open("testresources/kramnik.pgn.gz").unwrap().read_to_end(&mut bytes).unwrap();
+    bytes.truncate(5000);
+
+    let p = ChessParserBuilder::new().build();
+    let mut iterator = p.parse_reader(::base::compression::CompressedReader::new(&bytes[..]));
+    let mut games = 0;
+    while iterator.next_temp() {
+        games += 1;
+    }
+
+    assert!(games > 0 && games < 40);
+    assert_eq!(1, iterator.get_errors().iter().filter(|it| it.contains("cannot read line")).count());
+    assert!(!iterator.next_temp());
+}
+
+#[test]
+fn parse_bytes_capablanca() {
+    let mut builder = ChessParserBuilder::new();
+    builder.ignore_comments();
+    builder.ignore_variations();
+    let p = builder.build();
+
+    let file = File::open("testresources/Capablanca.pgn").unwrap();
+    let mmap = ::base::borrowed::map_file(&file).unwrap();
+
+    assert_eq!(p.parse_bytes(&mmap).unwrap().count(), 597);
+}
+
+#[test]
+fn parse_bytes_same_as_parse() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    for path in &["testresources/kramnik.pgn", "testresources/lexical.pgn", "testresources/commands.pgn"] {
+        let content = fs::read(path).unwrap();
+
+        let games : Vec<ChessGameImpl> = p.parse_bytes(&content).unwrap().map(|it| it.to_game()).collect();
+        let expected : Vec<ChessGameImpl> = collect(p.parse(File::open(path).unwrap()));
+
+        assert_eq!(expected.len(), games.len());
+
+        for (game, expected_game) in games.iter().zip(expected.iter()) {
+            assert_eq!(expected_game.get_tags(), game.get_tags());
+            assert_eq!(expected_game.get_moves(), game.get_moves());
+            assert_eq!(expected_game.get_game_result(), game.get_game_result());
+            for m in -1..game.get_moves().len() as i16 {
+                assert_eq!(expected_game.get_comment(m), game.get_comment(m));
+                assert_eq!(expected_game.get_nags(m), game.get_nags(m));
+                assert_eq!(expected_game.get_variations(m), game.get_variations(m));
+                assert_eq!(expected_game.get_after_variation_comment(m, 0), game.get_after_variation_comment(m, 0));
+                assert_eq!(expected_game.get_commands(m), game.get_commands(m));
+            }
+        }
+    }
+}
+
+#[test]
+fn parse_commands() {
+    use base::annotation::*;
+    use std::time::Duration;
+
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    let games = collect(p.parse(File::open("testresources/commands.pgn").unwrap()));
+    let game = &games[0];
+
+    let commands = game.get_commands(0).unwrap();
+    assert_eq!(Some(Duration::from_secs(300)), commands.clock);
+    assert_eq!(Some(Eval::Centipawns(17)), commands.eval);
+    assert_eq!(None, game.get_comment(0));
+
+    let commands = game.get_commands(1).unwrap();
+    assert_eq!(Some(Duration::from_millis(298_500)), commands.clock);
+    assert_eq!(Some(Eval::Centipawns(20)), commands.eval);
+    assert_eq!(Some(24), commands.eval_depth);
+
+    assert_eq!(Some(Duration::from_secs(4)), game.get_commands(2).unwrap().elapsed);
+    assert_eq!(Some(&"The queen comes out too early.".to_string()), game.get_comment(2));
+
+    let commands = game.get_commands(3).unwrap();
+    assert_eq!(vec![SquareHighlight{color: AnnotationColor::Green, square: "e5".to_string()},
+        SquareHighlight{color: AnnotationColor::Red, square: "h5".to_string()}], commands.highlights);
+    assert_eq!(vec![Arrow{color: AnnotationColor::Green, from: "b8".to_string(), to: "c6".to_string()},
+        Arrow{color: AnnotationColor::Red, from: "h5".to_string(), to: "f7".to_string()}], commands.arrows);
+
+    // unknown commands are kept in the comment
+    assert_eq!(Some(Eval::Centipawns(-45)), game.get_commands(4).unwrap().eval);
+    assert_eq!(Some(&"[%tqu \"En\",\"\",\"\"]".to_string()), game.get_comment(4));
+
+    assert_eq!(Some(Eval::Mate(-9)), game.get_commands(5).unwrap().eval);
+
+    // invalid commands too
+    assert_eq!(None, game.get_commands(6));
+    assert_eq!(Some(&" [%clk x]".to_string()), game.get_comment(6));
+}
+
+#[test]
+fn write_commands() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    fs::create_dir_all("target/tmp").unwrap();
+
+    let chess_writer_builder = ChessWriterBuilder::new();
+    let mut chess_writer = chess_writer_builder.build(File::create("target/tmp/commands_write.pgn").unwrap());
+
+    let expected = collect(p.parse(File::open("testresources/commands.pgn").unwrap()));
+    chess_writer.write(&expected[0]).unwrap();
+
+    let written = fs::read_to_string("target/tmp/commands_write.pgn").unwrap();
+    assert!(written.contains("2. Qh5 {[%clk 0:04:59] [%emt 0:00:04] The queen comes out too early.} "));
+    assert!(written.contains("{[%clk 0:04:58.5] [%eval 0.20,24]}"));
+
+    let games = collect(p.parse(File::open("target/tmp/commands_write.pgn").unwrap()));
+
+    for m in 0..expected[0].get_moves().len() as i16 {
+        assert_eq!(expected[0].get_commands(m), games[0].get_commands(m));
+        assert_eq!(expected[0].get_comment(m), games[0].get_comment(m));
+    }
+}
+
+#[test]
+fn parse_lexical() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    let games = collect(p.parse(File::open("testresources/lexical.pgn").unwrap()));
+
+    assert_eq!(2, games.len());
+
+    let game = &games[0];
+    assert_eq!(Some(&"The \"Immortal\" game".to_string()), game.get_tags().get("Event"));
+    assert_eq!(Some(&"C:\\games\\london.pgn".to_string()), game.get_tags().get("Site"));
+    assert_eq!(45, game.get_moves().len());
+    assert_eq!("1-0", game.get_game_result());
+    assert_eq!(Some(&"the King's Gambit".to_string()), game.get_comment(3));
+    assert_eq!("Nf6", game.get_moves()[9]);
+    assert_eq!(Some(&vec!["6... Qh5 {the queen stays on the h file} 7. d3".to_string()]), game.get_variations(11));
+    assert_eq!("Nh5", game.get_moves()[13]);
+    assert_eq!(Some(&"a comment after a move".to_string()), game.get_comment(13));
+
+    assert_eq!(vec!["d4", "d5"], *games[1].get_moves());
+    assert_eq!(Some(&"the end".to_string()), games[1].get_comment(1));
+}
+
+#[test]
+fn write_escaped_tags() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    fs::create_dir_all("target/tmp").unwrap();
+
+    let chess_writer_builder = ChessWriterBuilder::new();
+    let mut chess_writer = chess_writer_builder.build(File::create("target/tmp/lexical_write.pgn").unwrap());
+
+    let expected = collect(p.parse(File::open("testresources/lexical.pgn").unwrap()));
+
+    for game in &expected {
+        chess_writer.write(game).unwrap();
+    }
+
+    let written = fs::read_to_string("target/tmp/lexical_write.pgn").unwrap();
+    assert!(written.starts_with("[Event \"The \\\"Immortal\\\" game\"]\n[Site \"C:\\\\games\\\\london.pgn\"]\n"));
+
+    let games = collect(p.parse(File::open("target/tmp/lexical_write.pgn").unwrap()));
+    assert_eq!(expected[0].get_tags(), games[0].get_tags());
+}
+
+#[test]
+fn game_filters() {
+    use base::filter::*;
+    use std::sync::Arc;
+
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+    let all = collect(p.parse(File::open("testresources/kramnik.pgn").unwrap()));
+
+    let white = |tags: &IndexMap<String,String>| tags.get("White").is_some_and(|it| it.starts_with("Kramnik"));
+    let long = |game: &dyn ChessGame| game.get_moves().len() > 80;
+
+    let count = |filter: Arc<dyn GameFilter>| {
+        let mut builder = ChessParserBuilder::new();
+        builder.game_filter(filter);
+        let p = builder.build();
+        let games = collect(p.parse(File::open("testresources/kramnik.pgn").unwrap()));
+
+        let content = fs::read("testresources/kramnik.pgn").unwrap();
+        assert_eq!(games.len(), p.parse_bytes(&content).unwrap().count());
+
+        games.len()
+    };
+
+    let expected_white = all.iter().filter(|it| white(it.get_tags())).count();
+    let expected_long = all.iter().filter(|it| long(*it)).count();
+    let expected_both = all.iter().filter(|it| white(it.get_tags()) && long(*it)).count();
+
+    assert!(expected_both > 0 && expected_both < expected_white && expected_both < expected_long);
+
+    assert_eq!(expected_white, count(Arc::new(TagFilter::new(white))));
+    assert_eq!(expected_long, count(Arc::new(MovesFilter::new(long))));
+    assert_eq!(expected_both, count(Arc::new(And::new(vec![Box::new(TagFilter::new(white)), Box::new(MovesFilter::new(long))]))));
+    assert_eq!(expected_white + expected_long - expected_both,
+        count(Arc::new(Or::new(vec![Box::new(TagFilter::new(white)), Box::new(MovesFilter::new(long))]))));
+    assert_eq!(all.len() - expected_white, count(Arc::new(Not::new(Box::new(TagFilter::new(white))))));
+    assert_eq!(all.len() - expected_both,
+        count(Arc::new(Not::new(Box::new(And::new(vec![Box::new(TagFilter::new(white)), Box::new(MovesFilter::new(long))]))))));
+}
+
+#[test]
+fn position_filter() {
+    use base::filter::*;
+    use std::sync::Arc;
+
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+    let all = collect(p.parse(File::open("testresources/kramnik.pgn").unwrap()));
+
+    // the position after 1. d4 Nf6 2. c4 e6
+    let mut position = ChessPosition::initial_position();
+    for mv in ["d4", "Nf6", "c4", "e6"].iter() {
+        position.apply_move(mv);
+    }
+
+    let filter = Arc::new(PositionFilter::new(position));
+
+    let expected = all.iter().filter(|it| filter.accept_game(*it).unwrap_or(false)).count();
+    assert!(expected > 0 && expected < all.len());
+
+    let mut builder = ChessParserBuilder::new();
+    builder.game_filter(filter.clone());
+    let p = builder.build();
+
+    let mut parallel = p.parse_parallel(File::open("testresources/kramnik.pgn").unwrap(), 4);
+    parallel.set_chunk_size(10_000);
+
+    assert_eq!(expected, collect(p.parse(File::open("testresources/kramnik.pgn").unwrap())).len());
+    assert_eq!(expected, parallel.count());
+}
+
+#[test]
+fn progress_observer() {
+    use base::progress::ProgressObserver;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[derive(Default)]
+    struct Counter {
+        read: AtomicU64,
+        games: AtomicU64,
+        finished: AtomicU64,
+    }
+
+    impl ProgressObserver for Counter {
+        fn bytes_read(&self, read: u64, _total: u64) {
+            self.read.store(read, Ordering::Relaxed);
+        }
+
+        fn games_parsed(&self, count: u64) {
+            self.games.store(count, Ordering::Relaxed);
+        }
+
+        fn finished(&self) {
+            self.finished.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let size = fs::metadata("testresources/Capablanca.pgn").unwrap().len();
+    let expected = collect(ChessParserBuilder::new().build().parse(File::open("testresources/Capablanca.pgn").unwrap())).len();
+
+    for parallel in [false, true].iter() {
+        let counter = Arc::new(Counter::default());
+        let mut builder = ChessParserBuilder::new();
+        builder.file_size(size);
+        builder.progress_observer(counter.clone());
+        let p = builder.build();
+
+        let count = if *parallel {
+            let mut iterator = p.parse_parallel(File::open("testresources/Capablanca.pgn").unwrap(), 4);
+            iterator.set_chunk_size(50_000);
+            iterator.count()
+        } else {
+            collect(p.parse(File::open("testresources/Capablanca.pgn").unwrap())).len()
+        };
+
+        assert_eq!(expected, count);
+        assert_eq!(size, counter.read.load(Ordering::Relaxed));
+        assert_eq!(expected as u64, counter.games.load(Ordering::Relaxed));
+        assert_eq!(1, counter.finished.load(Ordering::Relaxed));
+    }
+
+    // the games rejected by the filter don't finish the progress of the indexed games
+    let kramnik_white = |tags: &IndexMap<String,String>| tags.get("White").is_some_and(|it| it.contains("Kramnik"));
+    let mut builder = ChessParserBuilder::new();
+    builder.tag_filter(kramnik_white);
+    let expected = collect(builder.build().parse(File::open("testresources/kramnik.pgn").unwrap())).len();
+    let index = GameIndex::build(&mut ChessParserBuilder::new().build()
+        .parse_seekable(File::open("testresources/kramnik.pgn").unwrap()).unwrap());
+    assert!(expected > 0 && expected < index.len());
+
+    let counter = Arc::new(Counter::default());
+    builder.progress_observer(counter.clone());
+    let count = builder.build().parse_indexed(File::open("testresources/kramnik.pgn").unwrap(), &index).unwrap().count();
+    assert_eq!(expected, count);
+    assert_eq!(expected as u64, counter.games.load(Ordering::Relaxed));
+    assert_eq!(1, counter.finished.load(Ordering::Relaxed));
+}
+
+#[test]
+fn build_game() {
+    use base::annotation::MoveCommands;
+    use base::nag::Nag;
+    use std::time::Duration;
+
+    let mut builder = ChessGameBuilder::new();
+    builder.tag("Event", "Built");
+    builder.tag("Result", "1-0");
+    for mv in ["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"].iter() {
+        builder.add_move(mv);
+    }
+    builder.before_moves_comment("A short game");
+    builder.comment(2, "too early");
+    builder.add_nag(5, Nag::PoorMove);
+    builder.add_variation(5, "3... g6");
+    builder.commands(6, MoveCommands{clock: Some(Duration::from_secs(290)), ..MoveCommands::default()});
+    builder.game_result("1-0");
+    let game = builder.build();
+
+    assert_eq!(Some(&"too early".to_string()), game.get_comment(2));
+    assert_eq!(vec![Nag::PoorMove], game.get_typed_nags(5));
+
+    fs::create_dir_all("target/tmp").unwrap();
+    let mut chess_writer = ChessWriterBuilder::new().build(File::create("target/tmp/built.pgn").unwrap());
+    chess_writer.write(&game).unwrap();
+
+    let games = collect(ChessParserBuilder::new().build().parse(File::open("target/tmp/built.pgn").unwrap()));
+    assert_eq!(game, games[0]);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serialize_games() {
+    extern crate serde_json;
+
+    let p = ChessParserBuilder::new().build();
+
+    for file in ["kramnik.pgn", "commands.pgn", "lexical.pgn"].iter() {
+        let games = collect(p.parse(File::open(format!("testresources/{}", file)).unwrap()));
+        let json = serde_json::to_string(&games).unwrap();
+        let read: Vec<ChessGameImpl> = serde_json::from_str(&json).unwrap();
+        assert_eq!(games, read, "{}", file);
+    }
+
+    let mut position = ChessPosition::initial_position();
+    position.apply_move("e4");
+    let json = serde_json::to_string(&position).unwrap();
+    assert!(json.contains("\"en_passant_target_square\":\"e3\""));
+    assert!(json.contains("\"WhitePawn\""));
+    assert!(position == serde_json::from_str::<ChessPosition>(&json).unwrap());
+
+    assert!(serde_json::from_str::<Square>("\"i9\"").is_err());
+    assert!(serde_json::from_str::<Square>("\"é\"").is_err());
+    assert!(serde_json::from_str::<Square>("\"š1\"").is_err());
+    assert_eq!("\"FEN\"", serde_json::to_string(&::base::tag::Tag::FEN).unwrap());
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn write_and_read_json() {
+    use base::json::*;
+    use base::writer::OutputFormat;
+
+    let p = ChessParserBuilder::new().build();
+    fs::create_dir_all("target/tmp").unwrap();
+
+    for file in ["variations.pgn", "kramnik.pgn", "commands.pgn"].iter() {
+        let expected = collect(p.parse(File::open(format!("testresources/{}", file)).unwrap()));
+        let expected_json: Vec<JsonGame> = expected.iter().map(|it| JsonGame::from_game(it)).collect();
+
+        for format in [OutputFormat::Json, OutputFormat::Ndjson].iter() {
+            let path = format!("target/tmp/{}.{}", file, format);
+            {
+                let mut builder = ChessWriterBuilder::new();
+                builder.format(*format);
+                let mut chess_writer = builder.build(File::create(&path).unwrap());
+                for game in &expected {
+                    chess_writer.write(game).unwrap();
+                }
+            }
+
+            let games = if *format == OutputFormat::Json {
+                read_json(File::open(&path).unwrap()).unwrap()
+            } else {
+                let mut iterator = NdjsonIterator::new(File::open(&path).unwrap());
+                let games: Vec<ChessGameImpl> = iterator.by_ref().collect();
+                assert!(iterator.get_errors().is_empty());
+                games
+            };
+
+            let json: Vec<JsonGame> = games.iter().map(|it| JsonGame::from_game(it)).collect();
+            assert_eq!(expected_json, json, "{} {}", file, format);
+        }
+    }
+
+    let games = collect(p.parse(File::open("testresources/variations.pgn").unwrap()));
+    let json = JsonGame::from_game(&games[0]);
+    let black = &json.moves[1].variations[0];
+    assert_eq!(Some("a comment after the variation".to_string()), black.after_comment);
+    assert_eq!("c5", black.moves[0].san);
+    assert_eq!(vec!["1".to_string()], black.moves[2].nags);
+    assert_eq!(Some("Alapin".to_string()), black.moves[1].variations[0].moves[0].comment);
+    assert_eq!("Nf6", json.moves[4].variations[0].moves[1].variations[0].moves[0].san);
+
+    let game = json.to_game().unwrap();
+    assert_eq!(Some(&vec!["1... c5 2. Nf3 (2. c3 {Alapin} 2... d5) 2... d6 $1 {Najdorf next}".to_string()]),
+        game.get_variations(1));
+
+    let game = JsonGame::from_game(&games[1]).to_game().unwrap();
+    assert_eq!(Some(&vec!["3. d4 exd4 4. Nxd4".to_string()]), game.get_variations(0));
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn read_ndjson_errors() {
+    use base::json::NdjsonIterator;
+
+    let input = "{\"tags\": {}, \"moves\": [{\"san\": \"e4\"}], \"result\": \"*\"}\n\nnot json\n{\"tags\": {}, \"moves\": [], \"result\": \"*\"}\n";
+    let mut iterator = NdjsonIterator::new(input.as_bytes());
+
+    assert_eq!(2, iterator.by_ref().count());
+    assert_eq!(1, iterator.get_errors().len());
+    assert!(iterator.get_errors()[0].starts_with("Error in line 3: "));
+}
+
+#[test]
+fn parse_in_other_thread() {
+    use std::thread;
+
+    let expected = collect(ChessParserBuilder::new().build().parse(File::open("testresources/kramnik.pgn").unwrap())).len();
+
+    let parser = {
+        let mut builder = ChessParserBuilder::new();
+        let event = "Dortmund".to_string();
+        builder.tag_filter(move |tags: &IndexMap<String,String>| tags.get("Event").is_some_and(|it| it.contains(&event)));
+        builder.build()
+    };
+    let filtered = thread::spawn(move || collect(parser.parse(File::open("testresources/kramnik.pgn").unwrap())).len());
+
+    let iterator = ChessParserBuilder::new().build().parse(File::open("testresources/kramnik.pgn").unwrap());
+    let all = thread::spawn(move || collect(iterator).len());
+
+    assert_eq!(expected, all.join().unwrap());
+    assert!(filtered.join().unwrap() < expected);
+}
+
+#[test]
+fn parse_parallel_same_as_parse() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    let mut parallel = p.parse_parallel(File::open("testresources/Capablanca.pgn").unwrap(), 4);
+    parallel.set_chunk_size(10_000);
+
+    let games : Vec<ChessGameImpl> = parallel.collect();
+    let expected : Vec<ChessGameImpl> = collect(p.parse(File::open("testresources/Capablanca.pgn").unwrap()));
+
+    assert_eq!(597, games.len());
+
+    for (game, expected_game) in games.iter().zip(expected.iter()) {
+        assert_eq!(expected_game.get_tags(), game.get_tags());
+        assert_eq!(expected_game.get_moves(), game.get_moves());
+    }
+}
+
+#[test]
+fn game_index_kramnik() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    let index = GameIndex::build(&mut p.parse_seekable(File::open("testresources/kramnik.pgn").unwrap()).unwrap());
+
+    assert_eq!(40, index.len());
+    assert_eq!(Some(&GameIndexEntry{offset: 0, line: 1}), index.get(0));
+
+    let expected : Vec<ChessGameImpl> = collect(p.parse(File::open("testresources/kramnik.pgn").unwrap()));
+
+    let mut iterator = p.parse_at(File::open("testresources/kramnik.pgn").unwrap(), index.get(10).unwrap()).unwrap();
+
+    assert!(iterator.next_temp());
+    assert_eq!(index.get(10), Some(&iterator.get_game_index_entry()));
+    assert_eq!(expected[10].get_moves(), iterator.get_moves());
+    assert_eq!(expected[11].get_tags(), iterator.next().unwrap().get_tags());
+
+    let mut query = GameIndex::new();
+    query.push(*index.get(3).unwrap());
+    query.push(*index.get(39).unwrap());
+
+    fs::create_dir_all("target/tmp").unwrap();
+    query.save("target/tmp/kramnik_query.idx").unwrap();
+    let query = GameIndex::load("target/tmp/kramnik_query.idx").unwrap();
+
+    let games : Vec<ChessGameImpl> = p.parse_indexed(File::open("testresources/kramnik.pgn").unwrap(), &query).unwrap().collect();
+
+    assert_eq!(2, games.len());
+    assert_eq!(expected[3].get_tags(), games[0].get_tags());
+    assert_eq!(expected[39].get_moves(), games[1].get_moves());
+
+    // the lines are one based
+    fs::write("target/tmp/invalid.idx", "0 0\n").unwrap();
+    assert!(GameIndex::load("target/tmp/invalid.idx").is_err());
+    let mut iterator = p.parse_at(File::open("testresources/kramnik.pgn").unwrap(), &GameIndexEntry{offset: 0, line: 0}).unwrap();
+    assert_eq!(expected[0].get_tags(), iterator.next().unwrap().get_tags());
+}
+
+#[test]
+fn game_index_compressed_not_supported() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    let entry = GameIndexEntry{offset: 0, line: 1};
+
+    assert!(p.parse_at(File::open("testresources/kramnik.pgn.gz").unwrap(), &entry).is_err());
+
+    // the index of a compressed file cannot be built
+    assert_eq!(Some("Cannot seek in a Gzip compressed file.".to_string()),
+        p.parse_seekable(File::open("testresources/kramnik.pgn.gz").unwrap()).err());
+}
+
+#[test]
+fn parse_windows_1252() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    let mut iterator = p.parse(File::open("testresources/latin1.pgn").unwrap());
+
+    assert!(iterator.next_temp());
+    assert_eq!(Some(&"Müller, José".to_string()), iterator.get_tags().get("White"));
+    assert_eq!(Some(&"Øberg, Åsa".to_string()), iterator.get_tags().get("Black"));
+    assert_eq!(Some(&"Philidor’s defence, the pin is a mistake".to_string()), iterator.get_comment(5));
+    assert_eq!(33, iterator.get_moves().len());
+    assert!(iterator.get_errors().is_empty());
+}
+
+#[test]
+fn parse_latin1_as_utf8() {
+    let mut builder = ChessParserBuilder::new();
+    builder.input_encoding(::base::encoding::Encoding::Utf8);
+    let p = builder.build();
+
+    let games = collect(p.parse(File::open("testresources/latin1.pgn").unwrap()));
+
+    assert_eq!(1, games.len());
+    assert_eq!(Some(&"M\u{FFFD}ller, Jos\u{FFFD}".to_string()), games[0].get_tags().get("White"));
+}
+
+#[test]
+fn parse_utf8_bom() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    let pgn = "\u{FEFF}[White \"Müller, José\"]\n\n1. e4 e5 1/2-1/2\n".to_string();
+
+    let games = collect(p.parse_string(&pgn));
+
+    assert_eq!(1, games.len());
+    assert_eq!(Some(&"Müller, José".to_string()), games[0].get_tags().get("White"));
+}
+
+#[test]
+fn write_windows_1252() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    fs::create_dir_all("target/tmp").unwrap();
+
+    let mut chess_writer_builder = ChessWriterBuilder::new();
+    chess_writer_builder.encoding(::base::encoding::Encoding::Windows1252);
+
+    let mut chess_writer = chess_writer_builder.build(File::create("target/tmp/latin1_write.pgn").unwrap());
+
+    for game in p.parse(File::open("testresources/latin1.pgn").unwrap()) {
+        chess_writer.write(&game).unwrap();
+    }
+
+    let written = fs::read("target/tmp/latin1_write.pgn").unwrap();
+
+    assert!(written.starts_with(b"[Event \"Caf\xE9 de la R\xE9gence\"]\n"));
+    assert!(written.windows(7).any(|it| it == b"\xD8berg, "));
+    assert!(written.windows(10).any(|it| it == b"Philidor\x92s"));
+}
+
 #[test]
 fn write_kramnik() {
     let chess_parser_builder = ChessParserBuilder::new();
@@ -55,6 +690,340 @@ fn write_kramnik() {
     }
 }
 
+#[test]
+fn write_wrapped_lines() {
+    let p = ChessParserBuilder::new().build();
+
+    fs::create_dir_all("target/tmp").unwrap();
+
+    for file in ["kramnik.pgn", "variations.pgn", "commands.pgn", "lexical.pgn"].iter() {
+        let expected = collect(p.parse(File::open(format!("testresources/{}", file)).unwrap()));
+        let path = format!("target/tmp/wrapped_{}", file);
+        {
+            let mut chess_writer = ChessWriterBuilder::new().build(File::create(&path).unwrap());
+            for game in &expected {
+                chess_writer.write(game).unwrap();
+            }
+        }
+
+        let written = fs::read_to_string(&path).unwrap();
+        for line in written.lines().filter(|it| !it.starts_with('[')) {
+            assert!(line.chars().count() <= 80, "{}", line);
+        }
+
+        let games = collect(p.parse(File::open(&path).unwrap()));
+        assert_eq!(expected, games);
+    }
+
+    let games: Vec<ChessGameImpl> = p.parse_string("1. e4 {a comment} e5 (1... c5 2. Nf3) {after} 2. Nf3 *").collect();
+    let mut chess_writer = ChessWriterBuilder::new().build(File::create("target/tmp/wrapped_numbers.pgn").unwrap());
+    chess_writer.write(&games[0]).unwrap();
+    drop(chess_writer);
+    assert_eq!("1. e4 {a comment} 1... e5 (1... c5 2. Nf3) {after} 2. Nf3 *\n\n",
+        fs::read_to_string("target/tmp/wrapped_numbers.pgn").unwrap());
+}
+
+#[test]
+fn write_to_vec() {
+    let p = ChessParserBuilder::new().build();
+    let games: Vec<ChessGameImpl> = p.parse_string("[White \"Kramnik\"]\n\n1. d4 Nf6 2. c4 *").collect();
+
+    let mut buffer = Vec::new();
+    {
+        let mut chess_writer = ChessWriterBuilder::new().build(&mut buffer);
+        chess_writer.write(&games[0]).unwrap();
+    }
+
+    assert_eq!("[White \"Kramnik\"]\n\n1. d4 Nf6 2. c4 *\n\n", String::from_utf8(buffer).unwrap());
+}
+
+#[test]
+fn write_canonical_tags() {
+    let p = ChessParserBuilder::new().build();
+    let pgn = "[White \"Kramnik\"]\n[ECO \"E32\"]\n[Black \"Anand\"]\n[MyTag \"x\"]\n[Event \"Open\"]\n\
+        [FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n[Annotator \"Me\"]\n\n1. e4 1/2-1/2";
+    let games: Vec<ChessGameImpl> = p.parse_string(pgn).collect();
+
+    let write = |builder: &ChessWriterBuilder| {
+        let mut buffer = Vec::new();
+        builder.build(&mut buffer).write(&games[0]).unwrap();
+        let written = String::from_utf8(buffer).unwrap();
+        written[..written.find("\n\n").unwrap()].to_string()
+    };
+
+    let mut builder = ChessWriterBuilder::new();
+    builder.canonical_tags();
+    assert_eq!("[Event \"Open\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"Kramnik\"]\n\
+        [Black \"Anand\"]\n[Result \"1/2-1/2\"]\n[ECO \"E32\"]\n[MyTag \"x\"]\n[SetUp \"1\"]\n\
+        [FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n[Annotator \"Me\"]", write(&builder));
+
+    let mut builder = ChessWriterBuilder::new();
+    builder.sort_tags();
+    builder.known_tags_only();
+    assert!(write(&builder).ends_with("[Result \"1/2-1/2\"]\n[Annotator \"Me\"]\n[ECO \"E32\"]\n[SetUp \"1\"]\n\
+        [FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]"));
+}
+
+#[test]
+fn write_stripped() {
+    let p = ChessParserBuilder::new().build();
+    let pgn = "[Annotator \"Kasparov, G.\"]\n\n1. e4! {[%clk 0:05:00] Best by test} e5 $2 \
+        (1... c5!? {[%eval 0.3] Sicilian} 2. Nf3 (2. c3 $6 {Alapin} (2. d4)) d6) {after} 2. Nf3 {[%emt 0:00:04]} *";
+    let games: Vec<ChessGameImpl> = p.parse_string(pgn).collect();
+
+    let write = |builder: &ChessWriterBuilder| {
+        let mut buffer = Vec::new();
+        builder.build(&mut buffer).write(&games[0]).unwrap();
+        let written = String::from_utf8(buffer).unwrap();
+        written[written.find("\n\n").unwrap() + 2..].replace('\n', " ")
+    };
+
+    let mut builder = ChessWriterBuilder::new();
+    builder.nonags();
+    builder.nocommands();
+    assert_eq!("1. e4 {Best by test} 1... e5 (1... c5 {Sicilian} 2. Nf3 (2. c3 {Alapin} (2. d4)) d6) {after} 2. Nf3 *  ",
+        write(&builder));
+
+    let mut builder = ChessWriterBuilder::new();
+    builder.variation_depth(1);
+    builder.annotator("kasparov");
+    assert_eq!("1. e4 $1 {[%clk 0:05:00] Best by test} 1... e5 $2 (1... c5!? {[%eval 0.3] Sicilian} 2. Nf3 d6) \
+        {after} 2. Nf3 {[%emt 0:00:04]} *  ", write(&builder));
+
+    let mut builder = ChessWriterBuilder::new();
+    builder.variation_depth(0);
+    builder.annotator("Karpov");
+    assert_eq!("1. e4 $1 {[%clk 0:05:00]} 1... e5 $2 2. Nf3 {[%emt 0:00:04]} *  ", write(&builder));
+}
+
+#[test]
+fn write_notations() {
+    use base::notation::*;
+
+    let p = ChessParserBuilder::new().build();
+    let pgn = "1. e4 d5 2. exd5 Qxd5 3. Nc3 (3. Nf3 Bg4 (3... Qe4+)) 3... Qa5 4. Bc4 Nf6 5. Nf3 Bg4 6. O-O e6 \
+        7. h3 Bh5 8. Bb5+ c6 *";
+    let games: Vec<ChessGameImpl> = p.parse_string(pgn).collect();
+
+    let write = |notation: &str| {
+        let mut builder = ChessWriterBuilder::new();
+        builder.notation(notation_from_name(notation).unwrap());
+        let mut buffer = Vec::new();
+        builder.build(&mut buffer).write(&games[0]).unwrap();
+        String::from_utf8(buffer).unwrap().replace('\n', " ")
+    };
+
+    assert_eq!("1. e2-e4 d7-d5 2. e4xd5 Qd8xd5 3. Nb1-c3 (3. Ng1-f3 Bc8-g4 (3... Qd5-e4+)) 3... Qd5-a5 \
+        4. Bf1-c4 Ng8-f6 5. Ng1-f3 Bc8-g4 6. O-O e7-e6 7. h2-h3 Bg4-h5 8. Bc4-b5+ c7-c6 *  ", write("lan"));
+    assert_eq!("1. e2e4 d7d5 2. e4d5 d8d5 3. b1c3 (3. g1f3 c8g4 (3... d5e4)) 3... d5a5 4. f1c4 g8f6 5. g1f3 \
+        c8g4 6. e1g1 e7e6 7. h2h3 g4h5 8. c4b5 c7c6 *  ", write("uci"));
+    assert!(write("figurine").starts_with("1. e4 d5 2. exd5 ♕xd5 3. ♘c3 (3. ♘f3 ♗g4 (3... ♕e4+)) 3... ♕a5"));
+    assert!(write("de").starts_with("1. e4 d5 2. exd5 Dxd5 3. Sc3 (3. Sf3 Lg4 (3... De4+)) 3... Da5 4. Lc4 Sf6"));
+    assert_eq!(pgn.to_string() + "\n\n", write("san").replace("  ", "\n\n"));
+
+    // a move that cannot be applied is written as read, with the next ones
+    let (games, _) = parse_with_errors(&ChessParserBuilder::new(), "1. e4 Né 2. Nf3 *");
+    let mut builder = ChessWriterBuilder::new();
+    builder.notation(Notation::Lan);
+    assert_eq!("1. e2-e4 Né 2. Nf3 *\n\n", write_to_string(&builder, &games));
+
+    let mut position = FENParserBuilder::new().build().parse("7k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+    let mv = position.apply_san_move("a8=Q+").unwrap();
+    assert_eq!("a7a8q", mv.to_notation(Notation::Uci));
+    assert_eq!("a7-a8=Q+", mv.to_notation(Notation::Lan));
+    assert_eq!("a8=D+", mv.to_notation(Notation::Localized(PieceLetters::from_language("de").unwrap())));
+    assert!(notation_from_name("xx").is_err());
+}
+
+#[test]
+fn parse_input_notations() {
+    use base::notation::*;
+
+    let parse = |notation: InputNotation, pgn: &str| {
+        let mut builder = ChessParserBuilder::new();
+        builder.input_notation(notation);
+        let mut iterator = builder.build().parse_string(pgn);
+        let mut games = Vec::new();
+        while iterator.next_temp() {
+            games.push(iterator.to_game());
+        }
+        (games, iterator.get_errors().clone())
+    };
+
+    let expected = vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "Qd6"];
+
+    let german = "1. e4 e5 2. Sf3 Sc6 3. Lb5 a6 (3... Sf6 4. O-O) 4. Lxc6 dxc6 5. O-O Dd6 *";
+    let (games, errors) = parse(InputNotation::Localized(PieceLetters::from_language("de").unwrap()), german);
+    assert!(errors.is_empty());
+    assert_eq!(expected, *games[0].get_moves());
+    assert_eq!(Some(&vec!["3... Nf6 4. O-O".to_string()]), games[0].get_variations(5));
+
+    let figurine = "1. e4 e5 2. ♘f3 ♞c6 3. ♗b5 a6 4. ♗xc6 dxc6 5. O-O ♛d6 *";
+    assert_eq!(expected, *parse(InputNotation::Figurine, figurine).0[0].get_moves());
+
+    let coordinates = "1. e2e4 e7e5 2. g1f3 b8c6 3. f1b5 a7a6 4. Bb5xc6 d7xc6 5. e1g1 d8-d6 *";
+    assert_eq!(expected, *parse(InputNotation::Coordinate, coordinates).0[0].get_moves());
+
+    let (games, errors) = parse(InputNotation::Auto, &format!("{}\n\n{}\n\n{}", figurine, coordinates, german));
+    assert!(errors.is_empty());
+    for game in &games {
+        assert_eq!(expected, *game.get_moves());
+    }
+
+    // the language is detected in the first game and kept for the second, where R is a king and not a rook
+    let (games, errors) = parse(InputNotation::Auto, "1. e4 e5 2. Cf3 Cc6 3. Fb5 a6 *\n\n1. e4 e5 2. Re2 Tb8 *");
+    assert!(errors.is_empty());
+    assert_eq!(vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"], *games[0].get_moves());
+    assert_eq!(vec!["e4", "e5", "Ke2", "Rb8"], *games[1].get_moves());
+
+    // the knight on d2 is told apart from the one on b1, and the pawn is promoted
+    let pgn = "[FEN \"4k3/P7/8/8/8/8/8/1N1NK3 w - - 0 1\"]\n\n1. b1c3 e8f7 2. a7a8n *";
+    let (games, _) = parse(InputNotation::Coordinate, pgn);
+    assert_eq!(vec!["Nbc3", "Kf7", "a8=N"], *games[0].get_moves());
+
+    let (games, errors) = parse(InputNotation::Coordinate, "1. e2e4 e2e4 *");
+    assert_eq!(vec!["e2e4", "e2e4"], *games[0].get_moves());
+    assert_eq!(1, errors.len());
+
+    // the variations aren't translated when the moves cannot be converted, and aren't replayed
+    let (games, errors) = parse(InputNotation::Auto, "1. e2e4 e7e5 2. e2e4 (2. ♘f3 b8c6) *");
+    assert_eq!(vec!["e2e4", "e7e5", "e2e4"], *games[0].get_moves());
+    assert_eq!(Some(&vec!["2. ♘f3 b8c6".to_string()]), games[0].get_variations(2));
+    assert_eq!(1, errors.len());
+
+    let (games, errors) = parse(InputNotation::Auto, "1. e2e4 e7e5 2. g1f3 (2. ♘c3 b8c6) *");
+    assert!(errors.is_empty());
+    assert_eq!(vec!["e4", "e5", "Nf3"], *games[0].get_moves());
+    assert_eq!(Some(&vec!["2. Nc3 b8c6".to_string()]), games[0].get_variations(2));
+}
+
+#[test]
+fn iccf_notation() {
+    use base::notation::*;
+
+    let pgn = "[FEN \"8/1P3k2/8/8/8/8/8/R3K3 w Q - 0 1\"]\n\n1. 5131 6777 2. 27284 7766 3. 4142 *\n\n\
+        1. 5254 5755 2. 7163 2836 3. 6125 1716 4. 2536 4736 5. 5171 4846 6 4244 5544 7 6344 *";
+
+    let mut builder = ChessParserBuilder::new();
+    builder.input_notation(InputNotation::Iccf);
+    let parser = builder.build();
+    let mut iterator = parser.parse_string(pgn);
+    let mut games = Vec::new();
+    while iterator.next_temp() {
+        games.push(iterator.to_game());
+    }
+    assert_eq!(Vec::<String>::new(), *iterator.get_errors());
+
+    assert_eq!(vec!["O-O-O", "Kg7", "b8=N", "Kf6", "Rd2"], *games[0].get_moves());
+    assert_eq!(vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "Qd6", "d4", "exd4", "Nxd4"],
+        *games[1].get_moves());
+
+    // the moves are split the same way, without conversion
+    let borrowed: Vec<Vec<String>> = parser.parse_bytes(pgn.as_bytes()).unwrap()
+        .map(|it| it.to_game().get_moves().clone()).collect();
+    assert_eq!(vec!["5131", "6777", "27284", "7766", "4142"], borrowed[0]);
+    assert_eq!(13, borrowed[1].len());
+
+    let mut builder = ChessWriterBuilder::new();
+    builder.notation(notation_from_name("iccf").unwrap());
+    let mut buffer = Vec::new();
+    builder.build(&mut buffer).write(&games[0]).unwrap();
+    assert!(String::from_utf8(buffer).unwrap().contains("1. 5131 6777 2. 27284 7766 3. 4142 *"));
+
+    // in SAN the numeric moves are rejected
+    let builder = ChessParserBuilder::new();
+    let (games, errors) = parse_with_errors(&builder, "1. e4 e5 2. 7163 Nc6 *");
+    assert_eq!(vec!["e4", "e5", "Nc6"], *games[0].get_moves());
+    assert_eq!(vec!["Error in line 1: unknown move 7163."], errors);
+    let (games, errors) = parse_with_errors(&builder, "1. e4 5755 *");
+    assert_eq!(vec!["e4"], *games[0].get_moves());
+    assert_eq!(vec!["Error in line 1: unknown move 5755."], errors);
+    let borrowed = builder.build().parse_bytes(b"1. e4 5755 2. 7163 *").unwrap().next().unwrap().to_game();
+    assert_eq!(vec!["e4"], *borrowed.get_moves());
+}
+
+#[test]
+fn write_normalized() {
+    use base::writer::IllegalMoves;
+
+    let pgn = "[Result \"*\"]\n\n1. e4 e5 2. Ngf3 Nc6 3. Bc4 Nf6+ 4. 0-0 *\n\n\
+        [FEN \"7k/P7/6K1/8/8/8/8/8 w - - 0 1\"]\n\n1. a8Q+ 1-0\n\n\
+        [Result \"1-0\"]\n\n1. e4 e5 2. Ke3 Nc6 3. Nf3 1-0";
+
+    let parser = ChessParserBuilder::new().build();
+    let mut iterator = parser.parse_string(pgn);
+    let mut games = Vec::new();
+    while iterator.next_temp() {
+        games.push(iterator.to_game());
+    }
+    assert_eq!(vec!["e4", "e5", "Ngf3", "Nc6", "Bc4", "Nf6+", "0-0"], *games[0].get_moves());
+
+    let write = |illegal_moves: IllegalMoves| {
+        let mut builder = ChessWriterBuilder::new();
+        builder.normalize(illegal_moves);
+        let mut buffer = Vec::new();
+        {
+            let mut writer = builder.build(&mut buffer);
+            for game in &games {
+                writer.write(game).unwrap();
+            }
+        }
+        String::from_utf8(buffer).unwrap()
+    };
+
+    let flagged = write(IllegalMoves::Flag);
+    assert!(flagged.contains("1. e4 e5 2. Nf3 Nc6 3. Bc4 Nf6 4. O-O *"));
+    assert!(flagged.contains("1. a8=Q# 1-0"));
+    assert!(flagged.contains("[Result \"1-0\"]\n\n1. e4 e5 2. Ke3 {Illegal move} 2... Nc6 3. Nf3 1-0"));
+
+    let truncated = write(IllegalMoves::Truncate);
+    assert!(truncated.contains("1. e4 e5 2. Nf3 Nc6 3. Bc4 Nf6 4. O-O *"));
+    assert!(truncated.contains("[Result \"*\"]\n[OriginalResult \"1-0\"]\n\n1. e4 e5 *"));
+
+    // malformed moves are flagged too
+    let (games, _) = parse_with_errors(&ChessParserBuilder::new(), "1. e4 Né 2. Nf3 *\n\n1. e4 N *\n\n1. e4 e5 2. Nfé3 *");
+    let mut builder = ChessWriterBuilder::new();
+    builder.normalize(IllegalMoves::Flag);
+    assert_eq!("1. e4 Né {Illegal move} 2. Nf3 *\n\n1. e4 N {Illegal move} *\n\n1. e4 e5 2. Nfé3 {Illegal move} *\n\n",
+        write_to_string(&builder, &games));
+    for mv in ["é", "Né", "+", "Ké1", "e8é"].iter() {
+        assert!(ChessPosition::initial_position().apply_san_move(mv).is_err(), "{}", mv);
+    }
+}
+
+#[test]
+fn write_enriched() {
+    let pgn = "[Result \"*\"]\n[PlyCount \"3\"]\n\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# *\n\n\
+        [Result \"1-0\"]\n\n1. d4 d5 *";
+
+    let parser = ChessParserBuilder::new().build();
+    let mut iterator = parser.parse_string(pgn);
+    let mut games = Vec::new();
+    while iterator.next_temp() {
+        games.push(iterator.to_game());
+    }
+
+    let mut builder = ChessWriterBuilder::new();
+    builder.enrich();
+    builder.final_fen();
+    builder.termination();
+    let mut buffer = Vec::new();
+    {
+        let mut writer = builder.build(&mut buffer);
+        for game in &games {
+            writer.write(game).unwrap();
+        }
+    }
+    let output = String::from_utf8(buffer).unwrap();
+
+    assert!(output.contains("[Result \"1-0\"]\n[PlyCount \"7\"]\n[OriginalResult \"*\"]\n\
+        [FinalFEN \"r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4\"]\n\
+        [Termination \"normal\"]\n\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0"));
+    assert!(output.contains("[Result \"*\"]\n[PlyCount \"2\"]\n[OriginalResult \"1-0\"]\n\
+        [FinalFEN \"rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq d6 0 2\"]\n\
+        [Termination \"unterminated\"]\n\n1. d4 d5 *"));
+}
+
 #[test]
 fn write_kramnik_ignore() {
     let mut chess_parser_builder = ChessParserBuilder::new();
@@ -153,6 +1122,23 @@ fn parse_variations_test() {
     assert_eq!("25... Qh4 $5 $11 {is interesting}", variations.unwrap()[0]);
 }
 
+#[test]
+fn parse_nested_variations_and_glyphs() {
+    let p = ChessParserBuilder::new().build();
+    let pgn = "1. e4 (1. d4 d5 (1... Nf6 2. c4)) 1... e5 $2{Bad} 2. Nf3 $14(2. Nc3) Nc6 *";
+    let games: Vec<ChessGameImpl> = p.parse_string(pgn).collect();
+    let game = &games[0];
+
+    // the char after a glyph was added to the next move, it was read as e4 e5 2{Bad} Nf3 Nc3) Nc6
+    assert_eq!(vec!["e4", "e5", "Nf3", "Nc6"], *game.get_moves());
+    assert_eq!(Some(&vec!["2".to_string()]), game.get_nags(1));
+    assert_eq!(Some(&"Bad".to_string()), game.get_comment(1));
+    assert_eq!(Some(&vec!["14".to_string()]), game.get_nags(2));
+    assert_eq!(Some(&vec!["2. Nc3".to_string()]), game.get_variations(2));
+    // the parenthesis closing a nested variation was dropped, it was 1. d4 d5 (1... Nf6 2. c4
+    assert_eq!(Some(&vec!["1. d4 d5 (1... Nf6 2. c4)".to_string()]), game.get_variations(0));
+}
+
 #[test]
 fn parse_with_tags_filter_test() {    
     let mut builder = ChessParserBuilder::new();
@@ -194,6 +1180,81 @@ fn parse_string() {
     let games : Vec<ChessGameImpl> = p.parse_string(&"1. d4 Nf6 2. c4 e6 3. Nc3 *".to_string()).collect();
 
     assert_eq!(5, games[0].get_moves().len());
+
+    // the parser can be used as a trait object
+    let p: Box<dyn ChessParser> = Box::new(builder.build());
+    assert_eq!(1, p.parse_string("1. e4 *").count());
+}
+
+#[test]
+fn parse_suffix_annotations() {
+    use base::nag::Nag;
+
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    let pgn = "1. e4! e5?! 2. Nf3!! $14 Nc6 $200 3. Bb5?? a6!? 4. Ba4? *".to_string();
+
+    let games : Vec<ChessGameImpl> = p.parse_string(&pgn).collect();
+    let game = &games[0];
+
+    assert_eq!(vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4"], *game.get_moves());
+    assert_eq!(Some(&vec!["1".to_string()]), game.get_nags(0));
+    assert_eq!(vec![Nag::QuestionableMove], game.get_typed_nags(1));
+    assert_eq!(vec![Nag::VeryGoodMove, Nag::WhiteSlightAdvantage], game.get_typed_nags(2));
+    // not standard NAGs are kept, but they are not typed
+    assert_eq!(Some(&vec!["200".to_string()]), game.get_nags(3));
+    assert!(game.get_typed_nags(3).is_empty());
+    assert_eq!(vec![Nag::VeryPoorMove], game.get_typed_nags(4));
+    assert_eq!(vec![Nag::SpeculativeMove], game.get_typed_nags(5));
+    assert_eq!(vec![Nag::PoorMove], game.get_typed_nags(6));
+
+    let borrowed = p.parse_bytes(pgn.as_bytes()).unwrap().next().unwrap().to_game();
+    assert_eq!(game.get_moves(), borrowed.get_moves());
+    for m in 0..7 {
+        assert_eq!(game.get_nags(m), borrowed.get_nags(m));
+    }
+
+    assert_eq!("Black has a moderate advantage", Nag::BlackModerateAdvantage.description());
+    assert_eq!(Some("+-"), Nag::from_code(18).unwrap().symbol());
+    assert_eq!(139, Nag::BlackSevereTimeControlPressure.code());
+}
+
+#[test]
+fn write_nag_symbols() {
+    let builder = ChessParserBuilder::new();
+    let p = builder.build();
+
+    fs::create_dir_all("target/tmp").unwrap();
+
+    let games : Vec<ChessGameImpl> = p.parse_string(&"1. e4! e5?! 2. Nf3!! $14 Nc6 $200 *".to_string()).collect();
+
+    let chess_writer_builder = ChessWriterBuilder::new();
+    let mut chess_writer = chess_writer_builder.build(File::create("target/tmp/nags_write.pgn").unwrap());
+    chess_writer.write(&games[0]).unwrap();
+
+    let written = fs::read_to_string("target/tmp/nags_write.pgn").unwrap();
+    assert_eq!("1. e4 $1 e5 $6 2. Nf3 $3 $14 Nc6 $200 *\n\n", written);
+
+    let mut chess_writer_builder = ChessWriterBuilder::new();
+    chess_writer_builder.nag_symbols();
+    let mut chess_writer = chess_writer_builder.build(File::create("target/tmp/nags_symbols_write.pgn").unwrap());
+    chess_writer.write(&games[0]).unwrap();
+
+    let written = fs::read_to_string("target/tmp/nags_symbols_write.pgn").unwrap();
+    assert_eq!("1. e4! e5?! 2. Nf3!! $14 Nc6 $200 *\n\n", written);
+
+    // the symbols written are read back as the same NAGs
+    let pgn = "1. e4 $14 e5 $18 2. Nf3 $13 Nc6 $10 3. Bb5 $1 $3 a6 $22 $23 *";
+    let (games, _) = parse_with_errors(&builder, pgn);
+    let written = write_to_string(&chess_writer_builder, &games);
+    assert_eq!("1. e4 $14 e5 $18 2. Nf3 $13 Nc6 $10 3. Bb5! $3 a6 $22 $23 *\n\n", written);
+
+    let (read, _) = parse_with_errors(&builder, &written);
+    assert_eq!(games[0].get_moves(), read[0].get_moves());
+    for m in 0..6 {
+        assert_eq!(games[0].get_nags(m), read[0].get_nags(m));
+    }
 }
 
 #[test]
@@ -260,7 +1321,128 @@ fn king_in_check_knight() {
     assert_eq!(true, position.king_in_check(ChessColor::Black));
 }
 
-fn  collect<'a,R: Read>(mut it: ChessParserIterator<'a,R>) -> Vec<ChessGameImpl> {
+#[test]
+fn descriptive_notation() {
+    use base::notation::*;
+    use base::descriptive::DescriptiveLanguage;
+
+    let parse = |language: DescriptiveLanguage, pgn: &str| {
+        let mut builder = ChessParserBuilder::new();
+        builder.input_notation(InputNotation::Descriptive(language));
+        parse_with_errors(&builder, pgn)
+    };
+
+    let english = "1. P-K4 P-K4 2. Kt-KB3 N-QB3 3. B-N5 P-QR3 4. BxN QPxB 5. O-O B-KN5 6. P-KR3 P-KR4 \
+        7. PxB PxP 8. N-K1 Q-R5 9. P-KB3 P-N6 10. Q-K2 Q-R7 mate 0-1";
+    let expected = vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "Bg4", "h3", "h5", "hxg4",
+        "hxg4", "Ne1", "Qh4", "f3", "g3", "Qe2", "Qh2#"];
+    let (games, errors) = parse(DescriptiveLanguage::English, english);
+    assert!(errors.is_empty());
+    assert_eq!(expected, *games[0].get_moves());
+
+    let pgn = "[FEN \"4k3/1P6/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. P-N8=Q ch K-Q2 2. Q-N7 ch K-K3 *\n\n\
+        [FEN \"4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1\"]\n\n1. PxP e.p. K-Q2 *";
+    let (games, errors) = parse(DescriptiveLanguage::English, pgn);
+    assert!(errors.is_empty());
+    assert_eq!(vec!["b8=Q+", "Kd7", "Qb7+", "Ke6"], *games[0].get_moves());
+    assert_eq!(vec!["exd6", "Kd7"], *games[1].get_moves());
+
+    let spanish = "1. P4R P4R 2. C3AR C3AD 3. A5C P3TD 4. AxC PDxA 5. O-O A5CR *";
+    let (games, errors) = parse(DescriptiveLanguage::Spanish, spanish);
+    assert!(errors.is_empty());
+    assert_eq!(expected[..10], games[0].get_moves()[..]);
+
+    // a move matching two pieces is an error and the moves are kept as read
+    let (games, errors) = parse(DescriptiveLanguage::English, "1. P-K4 P-K4 2. N-B3 *");
+    assert_eq!(vec!["P-K4", "P-K4", "N-B3"], *games[0].get_moves());
+    assert!(errors[0].contains("ambiguous move N-B3"));
+
+    // the games written in descriptive notation are read back as they were
+    let games: Vec<ChessGameImpl> = ChessParserBuilder::new().build()
+        .parse(File::open("testresources/Capablanca.pgn").unwrap()).take(100).collect();
+
+    for (name, language) in &[("descriptive", DescriptiveLanguage::English),
+            ("descriptive:es", DescriptiveLanguage::Spanish)] {
+        let mut builder = ChessWriterBuilder::new();
+        builder.notation(notation_from_name(name).unwrap());
+
+        let (read, errors) = parse(*language, &write_to_string(&builder, &games));
+        assert_eq!(Vec::<String>::new(), errors);
+        assert_eq!(games.len(), read.len());
+        for (game, read) in games.iter().zip(read.iter()) {
+            let moves: Vec<&str> = game.get_moves().iter().map(|it| it.trim_end_matches(['+', '#'])).collect();
+            let read_moves: Vec<&str> = read.get_moves().iter().map(|it| it.trim_end_matches(['+', '#'])).collect();
+            assert_eq!(moves, read_moves);
+        }
+    }
+}
+
+#[test]
+fn write_fen() {
+    use base::fen::to_fen;
+
+    let mut position = ChessPosition::initial_position();
+    assert_eq!("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", to_fen(&position));
+
+    position.apply_move("e4");
+    assert_eq!("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", to_fen(&position));
+
+    for mv in ["e5", "Nf3", "Nc6", "Bc4", "Nf6", "O-O", "Rb8"].iter() {
+        assert_eq!(None, position.apply_move(mv));
+    }
+    assert_eq!("1rbqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 w k - 6 5", to_fen(&position));
+
+    let fen_parser = FENParserBuilder::new().build();
+    for fen in ["r3k2r/8/8/8/8/8/8/R3K2R b Qk - 12 40", "4k3/8/8/8/4p3/8/5P2/4K3 w - - 1 1"].iter() {
+        assert_eq!(*fen, to_fen(&fen_parser.parse(fen).unwrap()));
+    }
+}
+
+#[test]
+fn write_csv() {
+    use base::writer::{CsvColumn, OutputFormat};
+
+    let p = ChessParserBuilder::new().build();
+    let pgn = "[Event \"Open, round 1\"]\n[White \"Kramnik\"]\n[Black \"Say \\\"hi\\\"\"]\n[Result \"1-0\"]\n\n\
+        1. e4 e5 2. Nf3 Nc6 3. Bb5 1-0\n\n[Event \"Blitz\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 30\"]\n\n30... Kd7 31. e4 *";
+    let games: Vec<ChessGameImpl> = p.parse_string(pgn).collect();
+    assert_eq!("Say \"hi\"", games[0].get_tags()["Black"]);
+
+    fs::create_dir_all("target/tmp").unwrap();
+
+    let columns = "Event,Black,@plycount,@opening:4,@finalfen";
+    for format in [OutputFormat::Csv, OutputFormat::Tsv].iter() {
+        let path = format!("target/tmp/games.{}", format);
+        {
+            let mut builder = ChessWriterBuilder::new();
+            builder.format(*format);
+            builder.columns(columns.split(',').map(|it| CsvColumn::from_name(it).unwrap()).collect());
+            let mut chess_writer = builder.build(File::create(&path).unwrap());
+            for game in &games {
+                chess_writer.write(game).unwrap();
+            }
+        }
+
+        let written = fs::read_to_string(&path).unwrap();
+        if *format == OutputFormat::Csv {
+            assert_eq!("Event,Black,@plycount,@opening:4,@finalfen\n\
+                \"Open, round 1\",\"Say \"\"hi\"\"\",5,1. e4 e5 2. Nf3 Nc6,\
+                r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3\n\
+                Blitz,,2,30... Kd7 31. e4,8/3k4/8/8/4P3/8/8/4K3 b - e3 0 31\n", written);
+        } else {
+            assert_eq!("Event\tBlack\t@plycount\t@opening:4\t@finalfen\n\
+                Open, round 1\tSay \"hi\"\t5\t1. e4 e5 2. Nf3 Nc6\t\
+                r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3\n\
+                Blitz\t\t2\t30... Kd7 31. e4\t8/3k4/8/8/4P3/8/8/4K3 b - e3 0 31\n", written);
+        }
+    }
+
+    assert_eq!(Ok(CsvColumn::Opening(10)), CsvColumn::from_name("@Opening"));
+    assert!(CsvColumn::from_name("@unknown").is_err());
+    assert!(CsvColumn::from_name("@opening:x").is_err());
+}
+
+fn  collect<R: Read>(mut it: ChessParserIterator<R>) -> Vec<ChessGameImpl> {
     let mut result = Vec::new();
 
     while it.next_temp() {
@@ -269,6 +1451,29 @@ fn  collect<'a,R: Read>(mut it: ChessParserIterator<'a,R>) -> Vec<ChessGameImpl>
     result
 }
 
+#[cfg(test)]
+fn parse_with_errors(builder: &ChessParserBuilder, pgn: &str) -> (Vec<ChessGameImpl>, Vec<String>) {
+    let mut iterator = builder.build().parse_string(pgn);
+    let mut games = Vec::new();
+
+    while iterator.next_temp() {
+        games.push(iterator.to_game());
+    }
+    (games, iterator.get_errors().clone())
+}
+
+#[cfg(test)]
+fn write_to_string(builder: &ChessWriterBuilder, games: &[ChessGameImpl]) -> String {
+    let mut buffer = Vec::new();
+    {
+        let mut writer = builder.build(&mut buffer);
+        for game in games {
+            writer.write(game).unwrap();
+        }
+    }
+    String::from_utf8(buffer).unwrap()
+}
+
 fn filter_tags_by_event(tags: &IndexMap<String,String>) -> bool {
     tags.get("Event").map_or_else(|| false, |r| r == "Test")
 }
\ No newline at end of file